@@ -28,18 +28,24 @@
 )]
 
 use std::fmt;
+use std::net::Ipv4Addr;
+use std::net::Ipv6Addr;
 use std::ops::Deref;
 use std::sync::LazyLock;
 
 use anyhow::bail;
+use anyhow::Context as _;
 use anyhow::Result;
 use regex::Regex;
 
+mod pgp_user_id;
+pub use pgp_user_id::{parse_pgp_user_id, ParsedUserId};
+
 mod vcard;
-pub use vcard::{make_vcard, parse_vcard, VcardContact};
+pub use vcard::{make_vcard, parse_vcard, VcardContact, VcardEmail, VcardTel};
 
 /// Valid contact address.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ContactAddress(String);
 
 impl Deref for ContactAddress {
@@ -191,8 +197,258 @@ pub fn sanitize_bidi_characters(input_str: &str) -> String {
 
 /// Returns false if addr is an invalid address, otherwise true.
 pub fn may_be_valid_addr(addr: &str) -> bool {
-    let res = EmailAddress::new(addr);
-    res.is_ok()
+    is_valid_addr_spec(addr)
+}
+
+/// Returns true if `c` is a valid RFC 5322 `atext` character, i.e. usable
+/// unquoted in a `dot-atom-text` local part or domain.
+fn is_atext(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "!#$%&'*+-/=?^_`{|}~".contains(c)
+}
+
+/// Returns true if `c` is a valid `qtext` character inside a quoted-string:
+/// anything but a control character, `"` or `\` (the latter two need
+/// `quoted-pair` escaping). RFC 5322 technically restricts `qtext` further and
+/// handles embedded whitespace via `FWS`, but accepting any non-control
+/// character here is simpler and not observably different for valid input.
+fn is_qtext(c: char) -> bool {
+    !c.is_control() && c != '"' && c != '\\'
+}
+
+/// Skips one CFWS comment (`"(" *(ctext / quoted-pair / comment) ")"`),
+/// including nested comments and `\`-escaped characters, and returns the text
+/// following the closing `)`. Returns `None` if `s` doesn't start with `(` or
+/// the comment is never closed.
+fn skip_comment(s: &str) -> Option<&str> {
+    let mut rest = s.strip_prefix('(')?;
+    let mut depth = 1u32;
+    loop {
+        let mut chars = rest.chars();
+        match chars.next()? {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(chars.as_str());
+                }
+            }
+            '\\' => {
+                chars.next()?;
+            }
+            _ => {}
+        }
+        rest = chars.as_str();
+    }
+}
+
+/// Skips CFWS (folding whitespace and `(...)` comments) at the start of `s`.
+fn skip_cfws(s: &str) -> &str {
+    let mut rest = s.trim_start();
+    while let Some(after_comment) = skip_comment(rest) {
+        rest = after_comment.trim_start();
+    }
+    rest
+}
+
+/// Parses an RFC 5322 `dot-atom-text` (`1*atext *("." 1*atext)`) off the start
+/// of `s` and returns the remainder. Returns `None` if `s` doesn't start with
+/// at least one `atext` character; stops before a trailing or doubled `.`
+/// instead of failing, leaving it for the caller to reject.
+fn parse_dot_atom_text(s: &str) -> Option<&str> {
+    let after_first = s.trim_start_matches(is_atext);
+    if after_first.len() == s.len() {
+        return None;
+    }
+    let mut rest = after_first;
+    while let Some(after_dot) = rest.strip_prefix('.') {
+        let after_label = after_dot.trim_start_matches(is_atext);
+        if after_label.len() == after_dot.len() {
+            break;
+        }
+        rest = after_label;
+    }
+    Some(rest)
+}
+
+/// Parses an RFC 5322 `quoted-string` (`DQUOTE *(qtext / quoted-pair) DQUOTE`)
+/// off the start of `s` and returns the text after the closing quote.
+fn parse_quoted_string(s: &str) -> Option<&str> {
+    let mut chars = s.strip_prefix('"')?.chars();
+    loop {
+        match chars.next()? {
+            '"' => return Some(chars.as_str()),
+            '\\' => {
+                chars.next()?;
+            }
+            c if is_qtext(c) => {}
+            _ => return None,
+        }
+    }
+}
+
+/// Parses an RFC 5321 §4.1.3 address literal domain (`"[" ... "]"`) off the
+/// start of `s`, validates its content via [`AddressLiteral::parse`], and
+/// returns the text after the closing `]`. Returns `None` if `s` doesn't
+/// start with `[`, the `]` is missing, or the content isn't a valid literal.
+fn parse_domain_literal(s: &str) -> Option<&str> {
+    let inner_and_rest = s.strip_prefix('[')?;
+    let (content, rest) = inner_and_rest.split_once(']')?;
+    AddressLiteral::parse(content).ok()?;
+    Some(rest)
+}
+
+/// Returns whether `input` is a valid RFC 5322 `addr-spec`
+/// (`local-part "@" domain`), the real grammar behind [`may_be_valid_addr`].
+/// The local part may be a `dot-atom` or a `quoted-string`, the domain may be
+/// a `dot-atom` or an address literal (`[192.0.2.1]`), and surrounding `CFWS`
+/// comments (e.g. `(comment)`) are recognized and skipped.
+///
+/// [`EmailAddress::new`] remains the fast, permissive parser used to actually
+/// split an address once it is known to be valid; this function is only used
+/// for validation.
+fn is_valid_addr_spec(input: &str) -> bool {
+    let s = skip_cfws(input);
+    let after_local = if s.starts_with('"') {
+        match parse_quoted_string(s) {
+            Some(rest) => rest,
+            None => return false,
+        }
+    } else {
+        match parse_dot_atom_text(s) {
+            Some(rest) => rest,
+            None => return false,
+        }
+    };
+    let Some(after_at) = skip_cfws(after_local).strip_prefix('@') else {
+        return false;
+    };
+    let domain = skip_cfws(after_at);
+    let after_domain = if domain.starts_with('[') {
+        match parse_domain_literal(domain) {
+            Some(rest) => rest,
+            None => return false,
+        }
+    } else {
+        match parse_dot_atom_text(domain) {
+            Some(rest) => rest,
+            None => return false,
+        }
+    };
+    skip_cfws(after_domain).is_empty()
+}
+
+/// Returns the prefix of `original` that was consumed to arrive at
+/// `remaining`, given that `remaining` is some suffix of `original` produced
+/// by repeatedly stripping characters off its front.
+fn consumed<'a>(original: &'a str, remaining: &str) -> &'a str {
+    original
+        .get(..original.len() - remaining.len())
+        .unwrap_or(original)
+}
+
+/// Splits `input` at top-level commas (the separator in RFC 5322
+/// `mailbox-list`/`address-list` headers), skipping over commas that appear
+/// inside a quoted-string, a `(...)` comment, or an `<...>` angle-addr.
+fn split_top_level_commas(input: &str) -> Vec<&str> {
+    let mut segments = Vec::new();
+    let mut remaining = input;
+    loop {
+        let mut s = remaining;
+        let after_comma = loop {
+            if s.is_empty() {
+                break None;
+            }
+            if let Some(after) = skip_comment(s) {
+                s = after;
+                continue;
+            }
+            if let Some(after) = parse_quoted_string(s) {
+                s = after;
+                continue;
+            }
+            if let Some(after_bracket) = s.strip_prefix('<') {
+                s = after_bracket.split_once('>').map_or("", |(_, after)| after);
+                continue;
+            }
+            if let Some(after) = s.strip_prefix(',') {
+                break Some(after);
+            }
+            let mut chars = s.chars();
+            chars.next();
+            s = chars.as_str();
+        };
+        match after_comma {
+            Some(after_comma) => {
+                let segment = consumed(remaining, after_comma)
+                    .strip_suffix(',')
+                    .unwrap_or(remaining);
+                segments.push(segment);
+                remaining = after_comma;
+            }
+            None => {
+                segments.push(remaining);
+                return segments;
+            }
+        }
+    }
+}
+
+/// If `s` starts with a `name-addr` (`[display-name] angle-addr`), returns
+/// the raw `display-name` text before the `<`, the `addr-spec` text inside
+/// the angle brackets, and whatever follows the closing `>`. Returns `None`
+/// if there's no top-level `<...>` in `s` (a bare `addr-spec` mailbox).
+fn split_name_addr(s: &str) -> Option<(&str, &str, &str)> {
+    let mut scan = s;
+    loop {
+        if let Some(after) = skip_comment(scan) {
+            scan = after;
+            continue;
+        }
+        if let Some(after) = parse_quoted_string(scan) {
+            scan = after;
+            continue;
+        }
+        if let Some(after_bracket) = scan.strip_prefix('<') {
+            let name = consumed(s, scan);
+            let (addr_spec, remainder) = after_bracket.split_once('>')?;
+            return Some((name, addr_spec, remainder));
+        }
+        let mut chars = scan.chars();
+        chars.next()?;
+        scan = chars.as_str();
+    }
+}
+
+/// Parses a single `mailbox` (`name-addr` or a bare `addr-spec`) into its
+/// display name, sanitized via [`sanitize_name`], and its address. Returns
+/// `None` if `segment` doesn't contain a syntactically valid `addr-spec`.
+fn parse_mailbox(segment: &str) -> Option<(String, ContactAddress)> {
+    let s = skip_cfws(segment);
+    let (name, addr_spec) = match split_name_addr(s) {
+        Some((name, addr_spec, _trailing)) => (name.trim(), addr_spec.trim()),
+        None => ("", s),
+    };
+    let addr = ContactAddress::new(addr_spec).ok()?;
+    let name = if name.is_empty() {
+        String::new()
+    } else {
+        sanitize_name(name)
+    };
+    Some((name, addr))
+}
+
+/// Parses an RFC 5322 `mailbox-list` as found in `To`/`From`/`Cc`-style
+/// header values, e.g. `Alice <a@x.org>, "Doe, John" <j@y.org>, bob@z.org`,
+/// into each mailbox's sanitized display name and validated address. Unlike
+/// [`sanitize_name_and_addr`]'s `(.*)<(.*)>` heuristic, this understands
+/// quoting and comments, so commas and angle brackets inside a quoted display
+/// name don't get mistaken for separators. Mailboxes that don't contain a
+/// syntactically valid `addr-spec` are skipped.
+pub fn parse_mailbox_list(input: &str) -> Vec<(String, ContactAddress)> {
+    split_top_level_commas(input)
+        .into_iter()
+        .filter_map(parse_mailbox)
+        .collect()
 }
 
 /// Returns address lowercased,
@@ -221,13 +477,13 @@ pub fn addr_cmp(addr1: &str, addr2: &str) -> bool {
 /// # Example
 ///
 /// ```
-/// use deltachat_contact_tools::EmailAddress;
+/// use deltachat_contact_tools::{EmailAddress, EmailDomain};
 /// let email = match EmailAddress::new("someone@example.com") {
 ///     Ok(addr) => addr,
 ///     Err(e) => panic!("Error parsing address, error was {}", e),
 /// };
 /// assert_eq!(&email.local, "someone");
-/// assert_eq!(&email.domain, "example.com");
+/// assert_eq!(email.domain, EmailDomain::Name("example.com".to_string()));
 /// assert_eq!(email.to_string(), "someone@example.com");
 /// ```
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -236,7 +492,7 @@ pub struct EmailAddress {
     pub local: String,
 
     /// Email address domain.
-    pub domain: String,
+    pub domain: EmailDomain,
 }
 
 impl fmt::Display for EmailAddress {
@@ -247,6 +503,10 @@ impl fmt::Display for EmailAddress {
 
 impl EmailAddress {
     /// Performs a dead-simple parse of an email address.
+    ///
+    /// This is a fast path, not a validator: it only splits on the last `@`
+    /// and rejects a few obviously-wrong cases. Use [`may_be_valid_addr`] if
+    /// you actually need to check that an address conforms to RFC 5322.
     pub fn new(input: &str) -> Result<EmailAddress> {
         if input.is_empty() {
             bail!("empty string is not valid");
@@ -271,9 +531,13 @@ impl EmailAddress {
                 if domain.ends_with('.') {
                     bail!("Domain {domain:?} should not contain the dot in the end");
                 }
+                let domain = match domain.strip_prefix('[').and_then(|d| d.strip_suffix(']')) {
+                    Some(literal) => EmailDomain::AddressLiteral(AddressLiteral::parse(literal)?),
+                    None => EmailDomain::Name((*domain).to_string()),
+                };
                 Ok(EmailAddress {
                     local: (*local).to_string(),
-                    domain: (*domain).to_string(),
+                    domain,
                 })
             }
             _ => bail!("Email {:?} must contain '@' character", input),
@@ -281,6 +545,89 @@ impl EmailAddress {
     }
 }
 
+/// The domain portion of an [`EmailAddress`]: either an ordinary domain name,
+/// or an RFC 5321 §4.1.3 address literal such as `[192.0.2.1]`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum EmailDomain {
+    /// An ordinary domain name, e.g. `example.com`.
+    Name(String),
+    /// An address literal, e.g. `[192.0.2.1]` or `[IPv6:2001:db8::1]`.
+    AddressLiteral(AddressLiteral),
+}
+
+impl fmt::Display for EmailDomain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EmailDomain::Name(name) => write!(f, "{name}"),
+            EmailDomain::AddressLiteral(literal) => write!(f, "[{literal}]"),
+        }
+    }
+}
+
+/// The bracketed content of an RFC 5321 §4.1.3 address literal, as used e.g.
+/// by `user@[192.0.2.1]`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum AddressLiteral {
+    /// `IPv4-address-literal`.
+    V4(Ipv4Addr),
+    /// `IPv6-address-literal` (the leading `IPv6:` tag is implied, not
+    /// stored).
+    V6(Ipv6Addr),
+    /// `General-address-literal = Standardized-tag ":" 1*dcontent`, for any
+    /// tag other than `IPv6`.
+    Tagged {
+        /// The `Standardized-tag`.
+        tag: String,
+        /// The `dcontent` following the tag.
+        content: String,
+    },
+}
+
+impl fmt::Display for AddressLiteral {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AddressLiteral::V4(addr) => write!(f, "{addr}"),
+            AddressLiteral::V6(addr) => write!(f, "IPv6:{addr}"),
+            AddressLiteral::Tagged { tag, content } => write!(f, "{tag}:{content}"),
+        }
+    }
+}
+
+impl AddressLiteral {
+    /// Parses the bracketed content of an address literal (without the
+    /// surrounding `[` `]`), e.g. `192.0.2.1` or `IPv6:2001:db8::1`.
+    fn parse(content: &str) -> Result<AddressLiteral> {
+        if let Some(v6) = content.strip_prefix("IPv6:") {
+            return Ok(AddressLiteral::V6(
+                v6.parse()
+                    .with_context(|| format!("invalid IPv6-address-literal {content:?}"))?,
+            ));
+        }
+        if let Ok(v4) = content.parse() {
+            return Ok(AddressLiteral::V4(v4));
+        }
+        let (tag, value) = content.split_once(':').with_context(|| {
+            format!("{content:?} is neither an IP address literal nor a tagged General-address-literal")
+        })?;
+        if tag.is_empty() || !tag.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            bail!("invalid Standardized-tag {tag:?} in address literal {content:?}");
+        }
+        if value.is_empty() || !value.chars().all(is_dcontent) {
+            bail!("invalid dcontent {value:?} in address literal {content:?}");
+        }
+        Ok(AddressLiteral::Tagged {
+            tag: tag.to_string(),
+            content: value.to_string(),
+        })
+    }
+}
+
+/// Returns true if `c` is valid `dcontent` inside a `General-address-literal`
+/// (RFC 5321 §4.1.2): any US-ASCII graphic character except `[`, `\` and `]`.
+fn is_dcontent(c: char) -> bool {
+    matches!(c as u32, 33..=90 | 94..=126)
+}
+
 impl rusqlite::types::ToSql for EmailAddress {
     fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
         let val = rusqlite::types::Value::Text(self.to_string());
@@ -312,14 +659,14 @@ mod tests {
             EmailAddress::new("user@domain.tld").unwrap(),
             EmailAddress {
                 local: "user".into(),
-                domain: "domain.tld".into(),
+                domain: EmailDomain::Name("domain.tld".into()),
             }
         );
         assert_eq!(
             EmailAddress::new("user@localhost").unwrap(),
             EmailAddress {
                 local: "user".into(),
-                domain: "localhost".into()
+                domain: EmailDomain::Name("localhost".into())
             }
         );
         assert_eq!(EmailAddress::new("uuu").is_ok(), false);
@@ -332,13 +679,127 @@ mod tests {
             EmailAddress::new("u@d.tt").unwrap(),
             EmailAddress {
                 local: "u".into(),
-                domain: "d.tt".into(),
+                domain: EmailDomain::Name("d.tt".into()),
             }
         );
         assert!(EmailAddress::new("u@tt").is_ok());
         assert_eq!(EmailAddress::new("@d.tt").is_ok(), false);
     }
 
+    #[test]
+    fn test_emailaddress_address_literal() {
+        assert_eq!(
+            EmailAddress::new("user@[192.0.2.1]").unwrap(),
+            EmailAddress {
+                local: "user".into(),
+                domain: EmailDomain::AddressLiteral(AddressLiteral::V4(
+                    "192.0.2.1".parse().unwrap()
+                )),
+            }
+        );
+        assert_eq!(
+            EmailAddress::new("user@[IPv6:2001:db8::1]").unwrap().domain,
+            EmailDomain::AddressLiteral(AddressLiteral::V6("2001:db8::1".parse().unwrap()))
+        );
+        assert_eq!(
+            EmailAddress::new("user@[tag:some-content]").unwrap().domain,
+            EmailDomain::AddressLiteral(AddressLiteral::Tagged {
+                tag: "tag".into(),
+                content: "some-content".into(),
+            })
+        );
+        assert!(EmailAddress::new("user@[256.0.0.1]").is_err());
+        assert!(EmailAddress::new("user@[not-an-ip]").is_err());
+        assert!(EmailAddress::new("user@[]").is_err());
+        assert_eq!(
+            EmailAddress::new("user@[192.0.2.1]").unwrap().to_string(),
+            "user@[192.0.2.1]"
+        );
+    }
+
+    #[test]
+    fn test_may_be_valid_addr() {
+        assert!(may_be_valid_addr("user@domain.tld"));
+        assert!(may_be_valid_addr("user@localhost"));
+        assert!(may_be_valid_addr("u@d"));
+        assert!(!may_be_valid_addr(""));
+        assert!(!may_be_valid_addr("uuu"));
+        assert!(!may_be_valid_addr("@d.tt"));
+        assert!(!may_be_valid_addr("u@d."));
+        assert!(!may_be_valid_addr("u@"));
+
+        // dot-atom-text allows most punctuation, but not two dots in a row
+        // or a leading/trailing one.
+        assert!(may_be_valid_addr("u.ser+tag@domain.tld"));
+        assert!(!may_be_valid_addr(".user@domain.tld"));
+        assert!(!may_be_valid_addr("user.@domain.tld"));
+        assert!(!may_be_valid_addr("us..er@domain.tld"));
+
+        // CFWS comments around the address are recognized and skipped.
+        assert!(may_be_valid_addr("(hi) user@domain.tld (bye)"));
+
+        // A quoted local part may contain characters that aren't allowed
+        // unquoted, such as spaces or `@`.
+        assert!(may_be_valid_addr("\"john doe\"@domain.tld"));
+        assert!(may_be_valid_addr("\"john@doe\"@domain.tld"));
+        assert!(!may_be_valid_addr("\"unterminated@domain.tld"));
+
+        // Address literals are recognized too, but their content is validated.
+        assert!(may_be_valid_addr("user@[192.0.2.1]"));
+        assert!(may_be_valid_addr("user@[IPv6:2001:db8::1]"));
+        assert!(may_be_valid_addr("user@[tag:some-content]"));
+        assert!(!may_be_valid_addr("user@[256.0.0.1]"));
+        assert!(!may_be_valid_addr("user@[not-an-ip]"));
+    }
+
+    #[test]
+    fn test_parse_mailbox_list() {
+        let parsed = parse_mailbox_list(
+            r#"Alice <alice@example.org>, "Doe, John" <j@example.org>, bob@example.org"#,
+        );
+        assert_eq!(
+            parsed,
+            vec![
+                (
+                    "Alice".to_string(),
+                    ContactAddress::new("alice@example.org").unwrap()
+                ),
+                (
+                    "Doe, John".to_string(),
+                    ContactAddress::new("j@example.org").unwrap()
+                ),
+                (
+                    "".to_string(),
+                    ContactAddress::new("bob@example.org").unwrap()
+                ),
+            ]
+        );
+
+        // A comment and a `<`/`>`-containing display name don't get mistaken
+        // for mailbox separators or the angle-addr itself.
+        let parsed = parse_mailbox_list(r#""<3 Bot>" <bot@example.org> (this is a comment, ok)"#);
+        assert_eq!(
+            parsed,
+            vec![(
+                "<3 Bot>".to_string(),
+                ContactAddress::new("bot@example.org").unwrap()
+            )]
+        );
+
+        // Entries without a valid addr-spec are skipped rather than failing
+        // the whole list.
+        let parsed = parse_mailbox_list("Alice <alice@example.org>, not an address, <>");
+        assert_eq!(
+            parsed,
+            vec![(
+                "Alice".to_string(),
+                ContactAddress::new("alice@example.org").unwrap()
+            )]
+        );
+
+        assert_eq!(parse_mailbox_list(""), vec![]);
+    }
+
     #[test]
     fn test_sanitize_name() {
         assert_eq!(&sanitize_name(" hello world   "), "hello world");
@@ -0,0 +1,140 @@
+use std::ops::Range;
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+/// The components of an OpenPGP "conventionally parsed" User ID, as
+/// byte-offset ranges into the original string (see [`parse_pgp_user_id`]).
+/// No part of the string is copied or unescaped, so a range can be sliced
+/// out of the original input whenever the caller actually needs the text.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ParsedUserId {
+    /// Byte range of the `Name` component, if present.
+    pub name: Option<Range<usize>>,
+    /// Byte range of the `Comment` component, excluding the parentheses, if present.
+    pub comment: Option<Range<usize>>,
+    /// Byte range of the `email` component, excluding angle brackets if it was wrapped in them,
+    /// if present.
+    pub email: Option<Range<usize>>,
+    /// Byte range of a bare `scheme://...` URI, if the whole User ID is one.
+    pub uri: Option<Range<usize>>,
+}
+
+/// Parses `user_id` as an OpenPGP "conventionally parsed" User ID (the
+/// `Name (Comment) <email>` convention used by most OpenPGP software for the
+/// content of a User ID packet). Recognizes, in order: `Name (Comment)
+/// <email>`, `Name <email>`, `<email>`, `Name (Comment)`, a bare `email`, and
+/// a bare `scheme://host/path` URI. Components that aren't present in
+/// `user_id`, or that don't match any of these forms at all, are `None`.
+pub fn parse_pgp_user_id(user_id: &str) -> ParsedUserId {
+    static NAME_COMMENT_EMAIL: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"^(?P<name>[^<(]*?)\s*\((?P<comment>[^()]*)\)\s*<(?P<email>[^<>]*)>\s*$")
+            .unwrap()
+    });
+    static NAME_EMAIL: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"^(?P<name>[^<(]*?)\s*<(?P<email>[^<>]*)>\s*$").unwrap());
+    static EMAIL_ONLY: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"^<(?P<email>[^<>]*)>\s*$").unwrap());
+    static NAME_COMMENT: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"^(?P<name>[^<(]*?)\s*\((?P<comment>[^()]*)\)\s*$").unwrap()
+    });
+    static BARE_EMAIL: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"^(?P<email>\S+@\S+)$").unwrap());
+    static URI: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"^(?P<uri>[A-Za-z][A-Za-z0-9+.\-]*://\S*)$").unwrap());
+
+    fn range_of(m: regex::Match) -> Range<usize> {
+        m.start()..m.end()
+    }
+
+    for re in [&*NAME_COMMENT_EMAIL, &*NAME_EMAIL, &*EMAIL_ONLY, &*NAME_COMMENT] {
+        if let Some(captures) = re.captures(user_id) {
+            return ParsedUserId {
+                name: captures
+                    .name("name")
+                    .filter(|m| !m.as_str().is_empty())
+                    .map(range_of),
+                comment: captures.name("comment").map(range_of),
+                email: captures.name("email").map(range_of),
+                uri: None,
+            };
+        }
+    }
+    if let Some(captures) = BARE_EMAIL.captures(user_id) {
+        return ParsedUserId {
+            email: captures.name("email").map(range_of),
+            ..Default::default()
+        };
+    }
+    if let Some(captures) = URI.captures(user_id) {
+        return ParsedUserId {
+            uri: captures.name("uri").map(range_of),
+            ..Default::default()
+        };
+    }
+    ParsedUserId::default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pgp_user_id_name_comment_email() {
+        let user_id = "Alice Wonderland (work) <alice@example.org>";
+        let parsed = parse_pgp_user_id(user_id);
+        assert_eq!(&user_id[parsed.name.unwrap()], "Alice Wonderland");
+        assert_eq!(&user_id[parsed.comment.unwrap()], "work");
+        assert_eq!(&user_id[parsed.email.unwrap()], "alice@example.org");
+        assert_eq!(parsed.uri, None);
+    }
+
+    #[test]
+    fn test_parse_pgp_user_id_name_email() {
+        let user_id = "Bob <bob@example.org>";
+        let parsed = parse_pgp_user_id(user_id);
+        assert_eq!(&user_id[parsed.name.unwrap()], "Bob");
+        assert_eq!(parsed.comment, None);
+        assert_eq!(&user_id[parsed.email.unwrap()], "bob@example.org");
+    }
+
+    #[test]
+    fn test_parse_pgp_user_id_email_only() {
+        let user_id = "<carol@example.org>";
+        let parsed = parse_pgp_user_id(user_id);
+        assert_eq!(parsed.name, None);
+        assert_eq!(&user_id[parsed.email.unwrap()], "carol@example.org");
+    }
+
+    #[test]
+    fn test_parse_pgp_user_id_bare_email() {
+        let user_id = "dave@example.org";
+        let parsed = parse_pgp_user_id(user_id);
+        assert_eq!(parsed.name, None);
+        assert_eq!(&user_id[parsed.email.unwrap()], "dave@example.org");
+    }
+
+    #[test]
+    fn test_parse_pgp_user_id_name_comment_only() {
+        let user_id = "Eve (no email here)";
+        let parsed = parse_pgp_user_id(user_id);
+        assert_eq!(&user_id[parsed.name.unwrap()], "Eve");
+        assert_eq!(&user_id[parsed.comment.unwrap()], "no email here");
+        assert_eq!(parsed.email, None);
+    }
+
+    #[test]
+    fn test_parse_pgp_user_id_uri() {
+        let user_id = "https://example.org/key";
+        let parsed = parse_pgp_user_id(user_id);
+        assert_eq!(parsed.name, None);
+        assert_eq!(parsed.email, None);
+        assert_eq!(&user_id[parsed.uri.unwrap()], user_id);
+    }
+
+    #[test]
+    fn test_parse_pgp_user_id_unrecognized() {
+        assert_eq!(parse_pgp_user_id("just a name"), ParsedUserId::default());
+        assert_eq!(parse_pgp_user_id(""), ParsedUserId::default());
+    }
+}
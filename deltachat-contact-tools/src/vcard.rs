@@ -6,18 +6,57 @@ use chrono::DateTime;
 use chrono::NaiveDateTime;
 use regex::Regex;
 
+use crate::addr_cmp;
+use crate::parse_pgp_user_id;
 use crate::sanitize_name_and_addr;
 
+/// One `EMAIL` entry of a [`VcardContact`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct VcardEmail {
+    pub addr: String,
+    /// This entry's `PREF` parameter (lower is more preferred), or `None` if unset.
+    pub pref: Option<u32>,
+    /// This entry's `TYPE` parameter values (e.g. `work`, `home`), lowercased.
+    pub types: Vec<String>,
+}
+
+/// One `TEL` entry of a [`VcardContact`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct VcardTel {
+    pub number: String,
+    /// This entry's `PREF` parameter (lower is more preferred), or `None` if unset.
+    pub pref: Option<u32>,
+    /// This entry's `TYPE` parameter values (e.g. `cell`, `home`), lowercased.
+    pub types: Vec<String>,
+}
+
 #[derive(Debug)]
 /// A Contact, as represented in a VCard.
 pub struct VcardContact {
-    /// The email address, vcard property `email`
+    /// The contact's most preferred email address, vcard property `email`. Equal to
+    /// `emails.first().addr`.
     pub addr: String,
+    /// All of the contact's `EMAIL` entries, ordered by their `PREF` parameter (most preferred
+    /// first).
+    pub emails: Vec<VcardEmail>,
+    /// All of the contact's `TEL` entries.
+    pub tel: Vec<VcardTel>,
     /// This must be the name authorized by the contact itself, not a locally given name. Vcard
     /// property `fn`. Can be empty, one should use `display_name()` to obtain the display name.
     pub authname: String,
-    /// The contact's public PGP key in Base64, vcard property `key`
+    /// The contact's most preferred public PGP key in Base64, vcard property `key`. Equal to
+    /// `keys.first()`.
     pub key: Option<String>,
+    /// All of the contact's public PGP keys in Base64, vcard property `key`, ordered by their
+    /// `PREF` parameter (most preferred first). Some providers (e.g. Proton) publish more than
+    /// one key per contact, e.g. during a key rollover.
+    pub keys: Vec<String>,
+    /// Whether the contact wants encryption for messages addressed to it, Proton's
+    /// `X-PM-ENCRYPT` vcard property.
+    pub encrypt_preference: Option<bool>,
+    /// Whether the contact wants messages addressed to it signed, Proton's `X-PM-SIGN` vcard
+    /// property.
+    pub sign_preference: Option<bool>,
     /// The contact's profile image (=avatar) in Base64, vcard property `photo`
     pub profile_image: Option<String>,
     /// The biography, stored in the vcard property `note`
@@ -34,6 +73,144 @@ impl VcardContact {
             true => &self.addr,
         }
     }
+
+    /// Returns whether this contact's most preferred PGP key (`self.key`)
+    /// has a primary User ID whose email component disagrees with `addr`.
+    /// Returns `false`, i.e. no flagged mismatch, if there's no key, the key
+    /// can't be decoded, it has no User ID packet, or that User ID's email
+    /// component is empty (e.g. a bare `Name (Comment)` User ID).
+    pub fn key_email_mismatch(&self) -> bool {
+        let Some(key) = &self.key else {
+            return false;
+        };
+        let Some(decoded_key) = base64_decode(key) else {
+            return false;
+        };
+        let Some(user_id) = find_user_id_packet(&decoded_key) else {
+            return false;
+        };
+        let Some(user_id) = String::from_utf8(user_id).ok() else {
+            return false;
+        };
+        let parsed = parse_pgp_user_id(&user_id);
+        let Some(email) = parsed.email.and_then(|range| user_id.get(range)) else {
+            return false;
+        };
+        !addr_cmp(email, &self.addr)
+    }
+}
+
+/// A minimal RFC 4648 base64 decoder (standard alphabet, `=` padding),
+/// used to turn a vcard's `KEY` value back into the raw OpenPGP key bytes.
+/// Whitespace in `input` is ignored, since `KEY` values can end up wrapped
+/// across folded vcard lines. Returns `None` if `input` isn't valid base64.
+fn base64_decode(input: &str) -> Option<Vec<u8>> {
+    fn sextet(c: u8) -> Option<u8> {
+        match c {
+            b'A'..=b'Z' => Some(c - b'A'),
+            b'a'..=b'z' => Some(c - b'a' + 26),
+            b'0'..=b'9' => Some(c - b'0' + 52),
+            b'+' => Some(62),
+            b'/' => Some(63),
+            _ => None,
+        }
+    }
+
+    let bytes: Vec<u8> = input.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+    let data_len = bytes.iter().take_while(|&&b| b != b'=').count();
+    let data = bytes.get(..data_len)?;
+
+    let mut out = Vec::with_capacity(data_len * 3 / 4);
+    let mut group: Vec<u8> = Vec::with_capacity(4);
+    for &b in data {
+        group.push(sextet(b)?);
+        if let [a, b2, c, d] = group.as_slice() {
+            let (a, b2, c, d) = (*a, *b2, *c, *d);
+            out.push((a << 2) | (b2 >> 4));
+            out.push((b2 << 4) | (c >> 2));
+            out.push((c << 6) | d);
+            group.clear();
+        }
+    }
+    match group.as_slice() {
+        [] => {}
+        [a, b2] => {
+            let (a, b2) = (*a, *b2);
+            out.push((a << 2) | (b2 >> 4));
+        }
+        [a, b2, c] => {
+            let (a, b2, c) = (*a, *b2, *c);
+            out.push((a << 2) | (b2 >> 4));
+            out.push((b2 << 4) | (c >> 2));
+        }
+        _ => return None,
+    }
+    Some(out)
+}
+
+/// Scans `data` as a sequence of OpenPGP packets (RFC 4880 §4.2, both old-
+/// and new-format headers with definite lengths) and returns the body of the
+/// first User ID packet (tag 13) found, i.e. the primary User ID. Returns
+/// `None` if no User ID packet is found, or the framing is malformed or uses
+/// a partial body length (not needed for a single User ID packet).
+fn find_user_id_packet(data: &[u8]) -> Option<Vec<u8>> {
+    const USER_ID_TAG: u8 = 13;
+
+    let mut rest = data;
+    loop {
+        let &first = rest.first()?;
+        if first & 0x80 == 0 {
+            return None;
+        }
+        let (tag, header_len, body_len) = if first & 0x40 != 0 {
+            // New packet format (RFC 4880 §4.2.2).
+            let tag = first & 0x3f;
+            let &len_byte = rest.get(1)?;
+            match len_byte {
+                0..=191 => (tag, 2, len_byte as usize),
+                192..=223 => {
+                    let &b2 = rest.get(2)?;
+                    (tag, 3, (len_byte as usize - 192) * 256 + b2 as usize + 192)
+                }
+                255 => {
+                    let len_bytes: [u8; 4] = rest.get(2..6)?.try_into().ok()?;
+                    (tag, 6, u32::from_be_bytes(len_bytes) as usize)
+                }
+                // 192..=223 and 255 are handled above; 224..=254 is a partial
+                // body length, which we don't need to support here.
+                _ => return None,
+            }
+        } else {
+            // Old packet format (RFC 4880 §4.2.1).
+            let tag = (first >> 2) & 0x0f;
+            match first & 0x03 {
+                0 => {
+                    let &len = rest.get(1)?;
+                    (tag, 2, len as usize)
+                }
+                1 => {
+                    let len_bytes: [u8; 2] = rest.get(1..3)?.try_into().ok()?;
+                    (tag, 3, u16::from_be_bytes(len_bytes) as usize)
+                }
+                2 => {
+                    let len_bytes: [u8; 4] = rest.get(1..5)?.try_into().ok()?;
+                    (tag, 5, u32::from_be_bytes(len_bytes) as usize)
+                }
+                // Indeterminate length: only valid for the packet running to
+                // the end of the data, which we don't need to support here.
+                _ => return None,
+            }
+        };
+
+        let body = rest.get(header_len..header_len.checked_add(body_len)?)?;
+        if tag == USER_ID_TAG {
+            return Some(body.to_vec());
+        }
+        rest = rest.get(header_len.checked_add(body_len)?..)?;
+        if rest.is_empty() {
+            return None;
+        }
+    }
 }
 
 /// Returns a vCard containing given contacts.
@@ -50,18 +227,62 @@ pub fn make_vcard(contacts: &[VcardContact]) -> String {
         s.replace(',', "\\,")
     }
 
+    fn type_params(types: &[String]) -> String {
+        if types.is_empty() {
+            String::new()
+        } else {
+            format!(";TYPE={}", types.join(","))
+        }
+    }
+
     let mut res = "".to_string();
     for c in contacts {
         // Mustn't contain ',', but it's easier to escape than to error out.
         let addr = escape(&c.addr);
         let display_name = escape(c.display_name());
-        res += &format!(
-            "BEGIN:VCARD\r\n\
-             VERSION:4.0\r\n\
-             EMAIL:{addr}\r\n\
-             FN:{display_name}\r\n"
-        );
-        if let Some(key) = &c.key {
+        res += "BEGIN:VCARD\r\nVERSION:4.0\r\n";
+        if c.emails.len() > 1 {
+            // More than one address on the same card: keep each one's `PREF`/`TYPE` so the
+            // reader can tell which is preferred, instead of collapsing to just `addr`.
+            for (i, email) in c.emails.iter().enumerate() {
+                let params = type_params(&email.types);
+                let pref = email.pref.unwrap_or((i + 1) as u32);
+                res += &format!("EMAIL{params};PREF={pref}:{}\r\n", escape(&email.addr));
+            }
+        } else if let Some(email) = c.emails.first() {
+            // Still go through `type_params`/`PREF` so a single email's `TYPE`/`PREF` round-trip
+            // through `parse_vcard()` instead of being silently dropped; only add `PREF` if the
+            // caller actually set one, to keep the common case's output unchanged.
+            let params = type_params(&email.types);
+            match email.pref {
+                Some(pref) => {
+                    res += &format!("EMAIL{params};PREF={pref}:{}\r\n", escape(&email.addr))
+                }
+                None => res += &format!("EMAIL{params}:{}\r\n", escape(&email.addr)),
+            }
+        } else {
+            res += &format!("EMAIL:{addr}\r\n");
+        }
+        res += &format!("FN:{display_name}\r\n");
+        for t in &c.tel {
+            res += &format!("TEL{}:{}\r\n", type_params(&t.types), escape(&t.number));
+        }
+        if c.keys.len() > 1 {
+            // More than one key (e.g. during a key rollover): group them under `ITEM1` with
+            // their `PREF` so readers can tell which one is preferred, Proton-style.
+            for (i, key) in c.keys.iter().enumerate() {
+                res += &format!(
+                    "ITEM1.KEY;PREF={}:data:application/pgp-keys;base64\\,{key}\r\n",
+                    i + 1
+                );
+            }
+            if let Some(encrypt_preference) = c.encrypt_preference {
+                res += &format!("ITEM1.X-PM-ENCRYPT:{encrypt_preference}\r\n");
+            }
+            if let Some(sign_preference) = c.sign_preference {
+                res += &format!("ITEM1.X-PM-SIGN:{sign_preference}\r\n");
+            }
+        } else if let Some(key) = &c.key {
             res += &format!("KEY:data:application/pgp-keys;base64\\,{key}\r\n");
         }
         if let Some(profile_image) = &c.profile_image {
@@ -78,6 +299,162 @@ pub fn make_vcard(contacts: &[VcardContact]) -> String {
     res
 }
 
+/// One unfolded content line of a vCard, tokenized per RFC 6350 §3.3:
+/// `[group "."] name *(";" param) ":" value`, where a `param` is `param-name
+/// "=" param-value *("," param-value)` and a `param-value` is either a
+/// DQUOTE-delimited quoted-string (in which `:`, `;` and `,` are literal) or
+/// an unquoted run of characters. Group names are arbitrary, not just
+/// `item1`; search "group name" at
+/// <https://datatracker.ietf.org/doc/html/rfc6350> for more info.
+struct VcardLine<'a> {
+    name: &'a str,
+    /// `(param name, values)`, in line order. Legacy vCard 3 bare parameters (e.g. `TEL;CELL:`)
+    /// are stored as a `TYPE` param, matching how vCard 4 would have spelled the same thing.
+    params: Vec<(String, Vec<String>)>,
+    /// The value, still vCard-escaped (e.g. `\,` for a literal comma).
+    value: &'a str,
+}
+
+/// Splits off a property's vCard "group" prefix, e.g. `item1.EMAIL:...` becomes
+/// (`Some("item1")`, `"EMAIL:..."`). Vcards commonly use this to associate an `EMAIL` with a
+/// matching `KEY`; the actual group name varies, it isn't always `item1`.
+fn split_group(line: &str) -> (Option<&str>, &str) {
+    let name_end = line.find([';', ':']).unwrap_or(line.len());
+    let Some(header) = line.get(..name_end) else {
+        return (None, line);
+    };
+    match header.find('.') {
+        Some(dot) if dot > 0 => match (line.get(..dot), line.get(dot + 1..)) {
+            (Some(group), Some(rest)) => (Some(group), rest),
+            _ => (None, line),
+        },
+        _ => (None, line),
+    }
+}
+
+/// Splits `s` at the first top-level `;` or `:`, i.e. one that isn't inside a
+/// DQUOTE-delimited quoted-string, returning the text before it and the
+/// remainder starting with that delimiter. Returns `None` if there's no such
+/// delimiter (a malformed line with no value).
+fn split_top_level(s: &str) -> Option<(&str, &str)> {
+    let mut in_quotes = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ';' | ':' if !in_quotes => return Some((s.get(..i)?, s.get(i..)?)),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Splits a param-value on top-level commas (respecting quoted-strings) and strips the
+/// surrounding quotes, if any, off of each one.
+fn split_param_values(value_part: &str) -> Vec<String> {
+    fn unquote(s: &str) -> String {
+        match s.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+            Some(unquoted) => unquoted.to_string(),
+            None => s.to_string(),
+        }
+    }
+
+    let mut values = Vec::new();
+    let mut in_quotes = false;
+    let mut start = 0;
+    for (i, c) in value_part.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => {
+                if let Some(part) = value_part.get(start..i) {
+                    values.push(unquote(part));
+                }
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if let Some(part) = value_part.get(start..) {
+        values.push(unquote(part));
+    }
+    values
+}
+
+impl<'a> VcardLine<'a> {
+    /// Tokenizes one unfolded content line. Returns `None` if it doesn't even have a top-level
+    /// `:` introducing a value (so isn't a content line at all, e.g. `BEGIN:VCARD` is handled
+    /// separately by the caller before reaching here).
+    fn parse(line: &'a str) -> Option<VcardLine<'a>> {
+        // The vCard "group" prefix (e.g. `item1.EMAIL:...`) is used to associate an `EMAIL`
+        // with a matching `KEY`, but nothing here needs that association, so it's discarded
+        // after splitting it off the property name.
+        let (_group, rest) = split_group(line);
+
+        let name_end = rest.find([';', ':']).unwrap_or(rest.len());
+        let name = rest.get(..name_end)?;
+        let mut remainder = rest.get(name_end..)?;
+
+        let mut params: Vec<(String, Vec<String>)> = Vec::new();
+        while let Some(after_semi) = remainder.strip_prefix(';') {
+            let (chunk, next) = split_top_level(after_semi)?;
+            remainder = next;
+            match chunk.find('=') {
+                Some(eq) => {
+                    let param_name = chunk.get(..eq)?.to_ascii_uppercase();
+                    let value_part = chunk.get(eq + 1..)?;
+                    params.push((param_name, split_param_values(value_part)));
+                }
+                None if !chunk.is_empty() => {
+                    params.push(("TYPE".to_string(), vec![chunk.to_string()]));
+                }
+                None => {}
+            }
+        }
+
+        let value = remainder.strip_prefix(':')?;
+        Some(VcardLine {
+            name,
+            params,
+            value,
+        })
+    }
+
+    /// Whether this line's property name is `name`, case-insensitively.
+    fn is(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name)
+    }
+
+    /// Returns a param's values, matching `name` case-insensitively. Concatenates values from
+    /// repeated params of the same name, if any.
+    fn param(&self, name: &str) -> Vec<&str> {
+        self.params
+            .iter()
+            .filter(|(n, _)| n.eq_ignore_ascii_case(name))
+            .flat_map(|(_, values)| values.iter().map(String::as_str))
+            .collect()
+    }
+
+    /// Returns this line's `PREF` parameter, or `None` if it has none or it doesn't parse as a
+    /// number.
+    fn pref(&self) -> Option<u32> {
+        self.param("PREF").first()?.parse().ok()
+    }
+
+    /// Returns a property's `TYPE` parameter values (e.g. `work`, `home`), lowercased. Handles
+    /// both the vCard 4 `TYPE=work,home` form and the legacy vCard 3 form where each type is its
+    /// own bare parameter, e.g. `TEL;CELL:...` (folded into `TYPE` by [`VcardLine::parse`]).
+    fn types(&self) -> Vec<String> {
+        self.param("TYPE")
+            .iter()
+            .map(|t| t.to_ascii_lowercase())
+            .collect()
+    }
+
+    /// Returns the value with vCard backslash-escaping of `,` undone.
+    fn unescaped_value(&self) -> String {
+        self.value.replace("\\,", ",")
+    }
+}
+
 /// Parses `VcardContact`s from a given `&str`.
 pub fn parse_vcard(vcard: &str) -> Vec<VcardContact> {
     fn remove_prefix<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
@@ -89,68 +466,57 @@ pub fn parse_vcard(vcard: &str) -> Vec<VcardContact> {
             None
         }
     }
-    /// Returns (parameters, raw value) tuple.
-    fn vcard_property_raw<'a>(line: &'a str, property: &str) -> Option<(&'a str, &'a str)> {
-        let remainder = remove_prefix(line, property)?;
-        // If `s` is `EMAIL;TYPE=work:alice@example.com` and `property` is `EMAIL`,
-        // then `remainder` is now `;TYPE=work:alice@example.com`
-
-        // Note: This doesn't handle the case where there are quotes around a colon,
-        // like `NAME;Foo="Some quoted text: that contains a colon":value`.
-        // This could be improved in the future, but for now, the parsing is good enough.
-        let (mut params, value) = remainder.split_once(':')?;
-        // In the example from above, `params` is now `;TYPE=work`
-        // and `value` is now `alice@example.com`
-
-        if params
-            .chars()
-            .next()
-            .filter(|c| !c.is_ascii_punctuation() || *c == '_')
-            .is_some()
-        {
-            // `s` started with `property`, but the next character after it was not punctuation,
-            // so this line's property is actually something else
-            return None;
+    /// Returns the line's value parsed as a boolean, e.g. for Proton's
+    /// `X-PM-ENCRYPT:true`/`X-PM-SIGN:false`.
+    fn vcard_bool_property(line: &VcardLine) -> Option<bool> {
+        match line.unescaped_value().as_str() {
+            "true" | "TRUE" | "True" => Some(true),
+            "false" | "FALSE" | "False" => Some(false),
+            _ => None,
         }
-        if let Some(p) = remove_prefix(params, ";") {
-            params = p;
+    }
+    /// Returns the key's base64 payload, if this line is a recognized form of a `KEY` property:
+    /// either a `data:application/pgp-keys;base64,...` URI (the modern and "old Delta Chat"
+    /// forms), or a bare base64 value tagged `TYPE=PGP` (however that combines with
+    /// `ENCODING=BASE64`/`ENCODING=b`, in any order, and whether `PGP`/`BASE64` are given as
+    /// bare vCard 3 parameters or explicit vCard 4 `TYPE=`/`ENCODING=` ones).
+    fn base64_key(line: &VcardLine) -> Option<String> {
+        if !line.is("key") {
+            return None;
         }
-        if let Some(p) = remove_prefix(params, "PREF=1") {
-            params = p;
+        if let Some(b) = remove_prefix(line.value, "data:application/pgp-keys;base64\\,")
+            .or_else(|| remove_prefix(line.value, "data:application/pgp-keys;base64,"))
+        {
+            return Some(b.to_string());
         }
-        Some((params, value))
+        let is_pgp = line.types().iter().any(|t| t == "pgp");
+        let is_base64 = line
+            .param("ENCODING")
+            .iter()
+            .any(|e| e.eq_ignore_ascii_case("BASE64") || e.eq_ignore_ascii_case("b"))
+            || line.types().iter().any(|t| t == "base64");
+        (is_pgp && is_base64).then(|| line.value.to_string())
     }
-    /// Returns (parameters, unescaped value) tuple.
-    fn vcard_property<'a>(line: &'a str, property: &str) -> Option<(&'a str, String)> {
-        let (params, value) = vcard_property_raw(line, property)?;
-        // Some fields can't contain commas, but unescape them everywhere for safety.
-        Some((params, value.replace("\\,", ",")))
-    }
-    fn base64_key(line: &str) -> Option<&str> {
-        let (params, value) = vcard_property_raw(line, "key")?;
-        if params.eq_ignore_ascii_case("PGP;ENCODING=BASE64")
-            || params.eq_ignore_ascii_case("TYPE=PGP;ENCODING=b")
-        {
-            return Some(value);
-        }
-        remove_prefix(value, "data:application/pgp-keys;base64\\,")
-            // Old Delta Chat format.
-            .or_else(|| remove_prefix(value, "data:application/pgp-keys;base64,"))
-    }
-    fn base64_photo(line: &str) -> Option<&str> {
-        let (params, value) = vcard_property_raw(line, "photo")?;
-        if params.eq_ignore_ascii_case("JPEG;ENCODING=BASE64")
-            || params.eq_ignore_ascii_case("ENCODING=BASE64;JPEG")
-            || params.eq_ignore_ascii_case("TYPE=JPEG;ENCODING=b")
-            || params.eq_ignore_ascii_case("ENCODING=b;TYPE=JPEG")
-            || params.eq_ignore_ascii_case("ENCODING=BASE64;TYPE=JPEG")
-            || params.eq_ignore_ascii_case("TYPE=JPEG;ENCODING=BASE64")
+    /// Like [`base64_key`], but for `PHOTO` properties tagged `TYPE=JPEG`/`MEDIATYPE=image/jpeg`.
+    fn base64_photo(line: &VcardLine) -> Option<String> {
+        if !line.is("photo") {
+            return None;
+        }
+        if let Some(b) = remove_prefix(line.value, "data:image/jpeg;base64\\,")
+            .or_else(|| remove_prefix(line.value, "data:image/jpeg;base64,"))
         {
-            return Some(value);
+            return Some(b.to_string());
         }
-        remove_prefix(value, "data:image/jpeg;base64\\,")
-            // Old Delta Chat format.
-            .or_else(|| remove_prefix(value, "data:image/jpeg;base64,"))
+        let is_jpeg = line.types().iter().any(|t| t == "jpeg")
+            || line
+                .param("MEDIATYPE")
+                .iter()
+                .any(|m| m.eq_ignore_ascii_case("image/jpeg"));
+        let is_base64 = line
+            .param("ENCODING")
+            .iter()
+            .any(|e| e.eq_ignore_ascii_case("BASE64") || e.eq_ignore_ascii_case("b"));
+        (is_jpeg && is_base64).then(|| line.value.to_string())
     }
     fn parse_datetime(datetime: &str) -> Result<i64> {
         // According to https://www.rfc-editor.org/rfc/rfc6350#section-4.3.5, the timestamp
@@ -191,44 +557,38 @@ pub fn parse_vcard(vcard: &str) -> Vec<VcardContact> {
         }
 
         let mut display_name = None;
-        let mut addr = None;
-        let mut key = None;
+        let mut emails: Vec<VcardEmail> = Vec::new();
+        let mut tel: Vec<VcardTel> = Vec::new();
+        let mut keys: Vec<(u32, String)> = Vec::new();
+        let mut encrypt_preference = None;
+        let mut sign_preference = None;
         let mut photo = None;
         let mut biography = None;
         let mut datetime = None;
 
-        for mut line in lines.by_ref() {
-            if let Some(remainder) = remove_prefix(line, "item1.") {
-                // Remove the group name, if the group is called "item1".
-                // If necessary, we can improve this to also remove groups that are called something different that "item1".
-                //
-                // Search "group name" at https://datatracker.ietf.org/doc/html/rfc6350 for more infos.
-                line = remainder;
-            }
+        for raw_line in lines.by_ref() {
+            if raw_line.eq_ignore_ascii_case("END:VCARD") {
+                emails.sort_by_key(|e| e.pref.unwrap_or(u32::MAX));
+                let preferred_addr = emails.first().map(|e| e.addr.clone());
 
-            if let Some((_params, email)) = vcard_property(line, "email") {
-                addr.get_or_insert(email);
-            } else if let Some((_params, name)) = vcard_property(line, "fn") {
-                display_name.get_or_insert(name);
-            } else if let Some(k) = base64_key(line) {
-                key.get_or_insert(k);
-            } else if let Some(p) = base64_photo(line) {
-                photo.get_or_insert(p);
-            } else if let Some((_params, bio)) = vcard_property(line, "note") {
-                biography.get_or_insert(bio);
-            } else if let Some((_params, rev)) = vcard_property(line, "rev") {
-                datetime.get_or_insert(rev);
-            } else if line.eq_ignore_ascii_case("END:VCARD") {
                 let (authname, addr) = sanitize_name_and_addr(
                     &display_name.unwrap_or_default(),
-                    &addr.unwrap_or_default(),
+                    &preferred_addr.unwrap_or_default(),
                 );
 
+                keys.sort_by_key(|(pref, _)| *pref);
+                let keys: Vec<String> = keys.into_iter().map(|(_, key)| key).collect();
+
                 contacts.push(VcardContact {
                     authname,
                     addr,
-                    key: key.map(|s| s.to_string()),
-                    profile_image: photo.map(|s| s.to_string()),
+                    emails,
+                    tel,
+                    key: keys.first().cloned(),
+                    keys,
+                    encrypt_preference,
+                    sign_preference,
+                    profile_image: photo,
                     biography,
                     timestamp: datetime
                         .as_deref()
@@ -237,6 +597,42 @@ pub fn parse_vcard(vcard: &str) -> Vec<VcardContact> {
                 });
                 break;
             }
+
+            let Some(line) = VcardLine::parse(raw_line) else {
+                continue;
+            };
+
+            if line.is("email") {
+                emails.push(VcardEmail {
+                    addr: line.unescaped_value(),
+                    pref: line.pref(),
+                    types: line.types(),
+                });
+            } else if line.is("tel") {
+                tel.push(VcardTel {
+                    number: line.unescaped_value(),
+                    pref: line.pref(),
+                    types: line.types(),
+                });
+            } else if line.is("fn") {
+                display_name.get_or_insert(line.unescaped_value());
+            } else if let Some(k) = base64_key(&line) {
+                keys.push((line.pref().unwrap_or(u32::MAX), k));
+            } else if line.is("x-pm-encrypt") {
+                if let Some(b) = vcard_bool_property(&line) {
+                    encrypt_preference.get_or_insert(b);
+                }
+            } else if line.is("x-pm-sign") {
+                if let Some(b) = vcard_bool_property(&line) {
+                    sign_preference.get_or_insert(b);
+                }
+            } else if let Some(p) = base64_photo(&line) {
+                photo.get_or_insert(p);
+            } else if line.is("note") {
+                biography.get_or_insert(line.unescaped_value());
+            } else if line.is("rev") {
+                datetime.get_or_insert(line.unescaped_value());
+            }
         }
     }
 
@@ -88,16 +88,34 @@ fn test_make_and_parse_vcard() {
     let contacts = [
         VcardContact {
             addr: "alice@example.org".to_string(),
+            emails: vec![VcardEmail {
+                addr: "alice@example.org".to_string(),
+                pref: None,
+                types: vec![],
+            }],
+            tel: vec![],
             authname: "Alice Wonderland".to_string(),
             key: Some("[base64-data]".to_string()),
+            keys: vec!["[base64-data]".to_string()],
+            encrypt_preference: None,
+            sign_preference: None,
             profile_image: Some("image in Base64".to_string()),
             biography: Some("Hi, I'm Alice".to_string()),
             timestamp: Ok(1713465762),
         },
         VcardContact {
             addr: "bob@example.com".to_string(),
+            emails: vec![VcardEmail {
+                addr: "bob@example.com".to_string(),
+                pref: None,
+                types: vec![],
+            }],
+            tel: vec![],
             authname: "".to_string(),
             key: None,
+            keys: vec![],
+            encrypt_preference: None,
+            sign_preference: None,
             profile_image: None,
             biography: None,
             timestamp: Ok(0),
@@ -134,6 +152,9 @@ fn test_make_and_parse_vcard() {
             assert_eq!(parsed[i].addr, contacts[i].addr);
             assert_eq!(parsed[i].authname, contacts[i].authname);
             assert_eq!(parsed[i].key, contacts[i].key);
+            assert_eq!(parsed[i].keys, contacts[i].keys);
+            assert_eq!(parsed[i].emails, contacts[i].emails);
+            assert_eq!(parsed[i].tel, contacts[i].tel);
             assert_eq!(parsed[i].profile_image, contacts[i].profile_image);
             assert_eq!(
                 parsed[i].timestamp.as_ref().unwrap(),
@@ -143,6 +164,42 @@ fn test_make_and_parse_vcard() {
     }
 }
 
+#[test]
+fn test_make_and_parse_vcard_single_email_with_type() {
+    let contacts = [VcardContact {
+        addr: "alice@example.org".to_string(),
+        emails: vec![VcardEmail {
+            addr: "alice@example.org".to_string(),
+            pref: None,
+            types: vec!["work".to_string()],
+        }],
+        tel: vec![],
+        authname: "Alice Wonderland".to_string(),
+        key: None,
+        keys: vec![],
+        encrypt_preference: None,
+        sign_preference: None,
+        profile_image: None,
+        biography: None,
+        timestamp: Ok(0),
+    }];
+
+    let vcard = make_vcard(&contacts);
+    assert_eq!(
+        vcard,
+        "BEGIN:VCARD\r\n\
+         VERSION:4.0\r\n\
+         EMAIL;TYPE=work:alice@example.org\r\n\
+         FN:Alice Wonderland\r\n\
+         REV:19700101T000000Z\r\n\
+         END:VCARD\r\n"
+    );
+
+    let parsed = parse_vcard(&vcard);
+    assert_eq!(parsed.len(), 1);
+    assert_eq!(parsed[0].emails, contacts[0].emails);
+}
+
 #[test]
 fn test_vcard_android() {
     let contacts = parse_vcard(
@@ -166,15 +223,64 @@ END:VCARD
     assert_eq!(contacts[0].authname, "Bob".to_string());
     assert_eq!(contacts[0].key, None);
     assert_eq!(contacts[0].profile_image, None);
+    assert_eq!(
+        contacts[0].emails,
+        vec![VcardEmail {
+            addr: "bob@example.org".to_string(),
+            pref: None,
+            types: vec!["home".to_string()],
+        }]
+    );
+    assert_eq!(
+        contacts[0].tel,
+        vec![VcardTel {
+            number: "+1-234-567-890".to_string(),
+            pref: None,
+            types: vec!["cell".to_string()],
+        }]
+    );
 
     assert_eq!(contacts[1].addr, "alice@example.org".to_string());
     assert_eq!(contacts[1].authname, "Alice".to_string());
     assert_eq!(contacts[1].key, None);
     assert_eq!(contacts[1].profile_image, None);
+    assert_eq!(contacts[1].tel, vec![]);
 
     assert_eq!(contacts.len(), 2);
 }
 
+#[test]
+fn test_vcard_multiple_emails() {
+    let contacts = parse_vcard(
+        "BEGIN:VCARD
+VERSION:4.0
+FN:Alice Wonderland
+EMAIL;TYPE=work;PREF=2:alice.work@example.org
+EMAIL;TYPE=home;PREF=1:alice.home@example.org
+END:VCARD",
+    );
+
+    assert_eq!(contacts.len(), 1);
+    // The lowest-PREF (most preferred) address becomes `addr`, but every address survives
+    // in `emails` instead of being silently discarded.
+    assert_eq!(&contacts[0].addr, "alice.home@example.org");
+    assert_eq!(
+        contacts[0].emails,
+        vec![
+            VcardEmail {
+                addr: "alice.home@example.org".to_string(),
+                pref: Some(1),
+                types: vec!["home".to_string()],
+            },
+            VcardEmail {
+                addr: "alice.work@example.org".to_string(),
+                pref: Some(2),
+                types: vec!["work".to_string()],
+            },
+        ]
+    );
+}
+
 #[test]
 fn test_vcard_local_datetime() {
     let contacts = parse_vcard(
@@ -245,6 +351,15 @@ END:VCARD",
     assert_eq!(&contacts[0].addr, "alice@example.org");
     assert_eq!(&contacts[0].authname, "Alice Wonderland");
     assert_eq!(contacts[0].key.as_ref().unwrap(), "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa");
+    assert_eq!(
+        contacts[0].keys,
+        vec![
+            "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa".to_string(),
+            "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb".to_string(),
+        ]
+    );
+    assert_eq!(contacts[0].encrypt_preference, Some(true));
+    assert_eq!(contacts[0].sign_preference, Some(true));
     assert!(contacts[0].timestamp.is_err());
     assert_eq!(contacts[0].profile_image, None);
 }
@@ -273,6 +388,149 @@ END:VCARD",
     assert_eq!(&contacts[0].addr, "alice@example.org");
     assert_eq!(&contacts[0].authname, "Alice");
     assert_eq!(contacts[0].key.as_ref().unwrap(), "xsaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa==");
+    assert_eq!(contacts[0].keys, vec![contacts[0].key.clone().unwrap()]);
+    assert_eq!(contacts[0].encrypt_preference, None);
+    assert_eq!(contacts[0].sign_preference, None);
     assert!(contacts[0].timestamp.is_err());
     assert_eq!(contacts[0].profile_image.as_ref().unwrap(), "/9aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa/Z");
 }
+
+/// `tBlBbGljZSA8YWxpY2VAZXhhbXBsZS5vcmc+` is the base64 of a single old-format
+/// OpenPGP User ID packet (tag 13) with body `Alice <alice@example.org>`.
+#[test]
+fn test_key_email_mismatch_matching() {
+    let contacts = parse_vcard(
+        "BEGIN:VCARD
+VERSION:4.0
+FN:Alice
+EMAIL:alice@example.org
+KEY:data:application/pgp-keys;base64,tBlBbGljZSA8YWxpY2VAZXhhbXBsZS5vcmc+
+END:VCARD
+",
+    );
+    assert_eq!(contacts.len(), 1);
+    assert!(!contacts[0].key_email_mismatch());
+}
+
+#[test]
+fn test_key_email_mismatch_different_email() {
+    let contacts = parse_vcard(
+        "BEGIN:VCARD
+VERSION:4.0
+FN:Alice
+EMAIL:alice@example.org
+KEY:data:application/pgp-keys;base64,tBdBbGljZSA8ZXZlQGV4YW1wbGUub3JnPg==
+END:VCARD
+",
+    );
+    assert_eq!(contacts.len(), 1);
+    assert!(contacts[0].key_email_mismatch());
+}
+
+#[test]
+fn test_key_email_mismatch_no_email_in_key() {
+    let contacts = parse_vcard(
+        "BEGIN:VCARD
+VERSION:4.0
+FN:Alice
+EMAIL:alice@example.org
+KEY:data:application/pgp-keys;base64,tBBBbGljZSBXb25kZXJsYW5k
+END:VCARD
+",
+    );
+    assert_eq!(contacts.len(), 1);
+    assert!(!contacts[0].key_email_mismatch());
+}
+
+/// The User ID packet here is preceded by an unrelated (and truncated)
+/// public-key packet, to check that packet scanning skips over it.
+#[test]
+fn test_key_email_mismatch_skips_preceding_packet() {
+    let contacts = parse_vcard(
+        "BEGIN:VCARD
+VERSION:4.0
+FN:Alice
+EMAIL:alice@example.org
+KEY:data:application/pgp-keys;base64,mAMBAgO0GUFsaWNlIDxhbGljZUBleGFtcGxlLm
+ 9yZz4=
+END:VCARD
+",
+    );
+    assert_eq!(contacts.len(), 1);
+    assert!(!contacts[0].key_email_mismatch());
+}
+
+#[test]
+fn test_key_email_mismatch_no_key() {
+    let contacts = parse_vcard(
+        "BEGIN:VCARD
+VERSION:4.0
+FN:Alice
+EMAIL:alice@example.org
+END:VCARD
+",
+    );
+    assert_eq!(contacts.len(), 1);
+    assert!(!contacts[0].key_email_mismatch());
+}
+
+/// A quoted `TYPE` param value can contain a literal `:` without ending the property early,
+/// and a bare-quoted value is unquoted.
+#[test]
+fn test_vcard_quoted_param_value_with_colon() {
+    let contacts = parse_vcard(
+        "BEGIN:VCARD
+VERSION:4.0
+FN:Alice
+EMAIL;TYPE=\"work: primary\";PREF=1:alice@example.org
+END:VCARD
+",
+    );
+    assert_eq!(contacts.len(), 1);
+    assert_eq!(
+        contacts[0].emails,
+        vec![VcardEmail {
+            addr: "alice@example.org".to_string(),
+            pref: Some(1),
+            types: vec!["work: primary".to_string()],
+        }]
+    );
+}
+
+/// `PHOTO;MEDIATYPE=image/jpeg;ENCODING=b` is a vCard 4 form not covered by any of the legacy
+/// vCard 3 string literals the old ad-hoc parser enumerated.
+#[test]
+fn test_vcard_photo_mediatype_param() {
+    let contacts = parse_vcard(
+        "BEGIN:VCARD
+VERSION:4.0
+FN:Alice
+EMAIL:alice@example.org
+PHOTO;MEDIATYPE=image/jpeg;ENCODING=b:/9j/4AAQSkZJRgABAQAA
+END:VCARD
+",
+    );
+    assert_eq!(contacts.len(), 1);
+    assert_eq!(
+        contacts[0].profile_image.as_deref().unwrap(),
+        "/9j/4AAQSkZJRgABAQAA"
+    );
+}
+
+/// Groups are arbitrary names, not just `item1`, and are discarded the same way regardless of
+/// what they're called.
+#[test]
+fn test_vcard_arbitrary_group_name() {
+    let contacts = parse_vcard(
+        "BEGIN:VCARD
+VERSION:4.0
+FN:Alice
+CONTACT1.EMAIL;PREF=1:alice@example.org
+CONTACT1.KEY;PREF=1:data:application/pgp-keys;base64,aaaaaaaaaaaaaaaaaaaa
+END:VCARD
+",
+    );
+    assert_eq!(contacts.len(), 1);
+    assert_eq!(&contacts[0].addr, "alice@example.org");
+    assert_eq!(contacts[0].key.as_deref(), Some("aaaaaaaaaaaaaaaaaaaa"));
+}
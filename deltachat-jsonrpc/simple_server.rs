@@ -1,10 +1,154 @@
 // Basit JSON-RPC server - voice call özelliklerini test etmek için
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
 use std::io::{BufRead, BufReader, Write};
-use std::net::{TcpListener, TcpStream};
+use std::net::{SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::os::unix::net::{UnixListener, UnixStream};
 use std::thread;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use opus::{Application, Channels, Decoder as OpusDecoder, Encoder as OpusEncoder};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tungstenite::Message;
+
+/// Default address/path for each `--transport` gateway in [`main`].
+const DEFAULT_HTTP_ADDR: &str = "127.0.0.1:3000";
+const DEFAULT_WEBSOCKET_ADDR: &str = "127.0.0.1:3001";
+const DEFAULT_UNIX_SOCKET_PATH: &str = "/tmp/deltachat_voice_jsonrpc.sock";
+
+/// RTP/Opus media parameters for the audio path opened once a call reaches
+/// `Connected`. 20ms frames at 48kHz mono is the typical Opus/WebRTC default.
+const RTP_SAMPLE_RATE: u32 = 48_000;
+const RTP_FRAME_MILLIS: u32 = 20;
+const RTP_SAMPLES_PER_FRAME: usize = (RTP_SAMPLE_RATE as usize / 1000) * RTP_FRAME_MILLIS as usize;
+const RTP_OPUS_PAYLOAD_TYPE: u8 = 111;
+const RTP_VERSION: u8 = 2;
+
+/// Env var overriding the worker pool's thread count; falls back to the
+/// available core count if unset or unparseable.
+const WORKER_POOL_SIZE_ENV: &str = "DELTACHAT_JSONRPC_WORKERS";
+/// Jobs allowed to be queued or running at once (counted together) before
+/// `WorkerPool::try_submit` starts rejecting new connections.
+const WORKER_QUEUE_CAPACITY: usize = 64;
+
+/// Fixed-size pool of connection-handling worker threads, so a burst of
+/// clients can't make the server spawn an unbounded number of OS threads the
+/// way a `thread::spawn` per accepted connection would. Up to
+/// `WORKER_QUEUE_CAPACITY` jobs may be queued or running at once; once that's
+/// full, `try_submit` hands the item straight back unrun instead of blocking
+/// the accept loop, so the gateway can reject the connection and keep
+/// accepting new ones rather than piling up unbounded backlog under load.
+struct WorkerPool {
+    jobs: Sender<Box<dyn FnOnce() + Send + 'static>>,
+    in_flight: Arc<std::sync::atomic::AtomicUsize>,
+    capacity: usize,
+}
+
+impl WorkerPool {
+    /// Spawns `size` worker threads, each pulling jobs off the same queue
+    /// until every `WorkerPool` (and thus every `Sender`) handed out is
+    /// dropped.
+    fn new(size: usize, capacity: usize) -> Self {
+        let (tx, rx) = mpsc::channel::<Box<dyn FnOnce() + Send + 'static>>();
+        let rx = Arc::new(Mutex::new(rx));
+        for _ in 0..size {
+            let rx = rx.clone();
+            thread::spawn(move || loop {
+                let job = rx.lock().unwrap().recv();
+                match job {
+                    Ok(job) => job(),
+                    Err(_) => break,
+                }
+            });
+        }
+        Self {
+            jobs: tx,
+            in_flight: Arc::new(std::sync::atomic::AtomicUsize::new(0)),
+            capacity,
+        }
+    }
+
+    /// Hands `item` to `handler` on a worker thread, returning `Err(item)`
+    /// unrun instead if the pool already has `capacity` jobs queued or
+    /// running — e.g. so the caller can reject a connection with a 503
+    /// rather than leaving it to wait behind an unbounded backlog. Takes a
+    /// plain `fn` pointer plus the item rather than an arbitrary closure so
+    /// a rejected item is handed back as itself, not trapped inside a
+    /// type-erased `Box<dyn FnOnce()>` the caller can no longer unwrap.
+    fn try_submit<T: Send + 'static>(&self, item: T, handler: fn(T)) -> Result<(), T> {
+        use std::sync::atomic::Ordering;
+        let reserved = self
+            .in_flight
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                (n < self.capacity).then_some(n + 1)
+            })
+            .is_ok();
+        if !reserved {
+            return Err(item);
+        }
+
+        let in_flight = self.in_flight.clone();
+        let _ = self.jobs.send(Box::new(move || {
+            handler(item);
+            in_flight.fetch_sub(1, Ordering::SeqCst);
+        }));
+        Ok(())
+    }
+}
+
+/// Number of worker threads to run, from `DELTACHAT_JSONRPC_WORKERS` or
+/// (when unset/unparseable) the number of available CPU cores.
+fn worker_pool_size() -> usize {
+    std::env::var(WORKER_POOL_SIZE_ENV)
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or_else(|| std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4))
+}
+
+/// This gateway's JSON-RPC protocol version, returned from `handshake`.
+const PROTOCOL_VERSION: &str = "1.0";
+
+/// Optional feature families a client can opt into via `handshake`'s
+/// `capabilities` param. Methods belonging to a family not negotiated are
+/// rejected with `RpcError::unsupported_capability`; core call lifecycle
+/// methods (`start_voice_call`, `accept_voice_call`, ...) need no capability
+/// at all, same as before this existed.
+const SUPPORTED_CAPABILITIES: &[&str] = &["subscriptions", "webrtc", "batch"];
+
+/// The workspace's release date, via the same `release-date.in` file and
+/// format the main `deltachat` crate's `release::DATE` (see
+/// `src/release.rs`) is built from.
+const RELEASE_DATE: &str = include_str!("../release-date.in");
+
+/// Per-connection handshake state. `capabilities` is `None` until `handshake`
+/// runs, after which it holds the negotiated (requested ∩ supported) set.
+///
+/// The HTTP gateway hands out a fresh `ConnectionState` per request (since
+/// each request is its own connection there), so a standalone, non-batch
+/// HTTP call can never satisfy "handshake first" — a client using that
+/// transport has to send `handshake` as the first element of a JSON-RPC
+/// batch alongside the calls it actually wants to make. The WebSocket and
+/// Unix-socket gateways keep one `ConnectionState` alive for the life of
+/// their (possibly long-lived) connection instead, so `handshake` only has
+/// to run once, before any other request on that connection.
+#[derive(Debug, Default)]
+struct ConnectionState {
+    capabilities: Option<HashSet<String>>,
+}
+
+impl ConnectionState {
+    fn is_handshaken(&self) -> bool {
+        self.capabilities.is_some()
+    }
+
+    fn has_capability(&self, capability: &str) -> bool {
+        self.capabilities.as_ref().is_some_and(|caps| caps.contains(capability))
+    }
+}
 
 // Basit CallStatus enum
 #[derive(Debug, Clone)]
@@ -33,6 +177,288 @@ pub struct ActiveCall {
     remote_peer_id: String,
     is_incoming: bool,
     status: CallStatus,
+    /// Our own SDP, set by `start_call` (offer) or `send_answer` (answer).
+    local_sdp: Option<String>,
+    /// The remote peer's SDP, known up front for an offer-driven incoming
+    /// call; there's no second peer process in this demo to deliver one for
+    /// an outgoing call, so `resolve_rtp_endpoint` loops back to `local_sdp`'s
+    /// own port in that case instead.
+    remote_sdp: Option<String>,
+    ice_candidates: Vec<String>,
+    ssrc: u32,
+    /// Bound as soon as the call is created (mirroring real ICE candidate
+    /// gathering happening before the call is answered), so its port is
+    /// already known when `local_sdp` is generated.
+    rtp_socket: Arc<UdpSocket>,
+    /// Only `Some` once the call is `Connected` and the RTP send/receive
+    /// threads are actually running.
+    rtp_session: Option<Arc<RtpSession>>,
+}
+
+impl ActiveCall {
+    fn new(call_id: String, remote_peer_id: String, is_incoming: bool) -> std::io::Result<Self> {
+        Ok(Self {
+            call_id,
+            remote_peer_id,
+            is_incoming,
+            status: CallStatus::Ringing,
+            local_sdp: None,
+            remote_sdp: None,
+            ice_candidates: Vec::new(),
+            ssrc: rand_u32(),
+            rtp_socket: Arc::new(UdpSocket::bind("127.0.0.1:0")?),
+            rtp_session: None,
+        })
+    }
+}
+
+/// A call state transition or signaling update, broadcast to every
+/// subscriber registered via `subscribe_call_events` so clients can react to
+/// calls live instead of polling `get_voice_call_status`. `sdp`/`candidate`
+/// are only set when an SDP or ICE update is relayed: by `start_call` (its
+/// `Ringing` event carries the local offer) and by the signaling methods
+/// (`send_offer`, `send_answer`, `add_ice_candidate`); the other status
+/// transitions leave both `None`.
+#[derive(Debug, Clone, Serialize)]
+struct CallEvent {
+    call_id: String,
+    status: String,
+    timestamp: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sdp: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    candidate: Option<String>,
+}
+
+fn now_unix() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+/// Minimal RFC 4566 SDP scan for the fields needed to open the RTP path: the
+/// connection address (`c=IN IP4 <addr>`) and the audio port (`m=audio
+/// <port> ...`). Returns `None` if either is missing or unparseable.
+fn parse_sdp_rtp_endpoint(sdp: &str) -> Option<SocketAddr> {
+    let mut ip: Option<std::net::IpAddr> = None;
+    let mut port: Option<u16> = None;
+    for line in sdp.lines() {
+        let line = line.trim();
+        if let Some(rest) = line.strip_prefix("c=IN IP4 ") {
+            ip = rest.trim().parse().ok();
+        } else if let Some(rest) = line.strip_prefix("m=audio ") {
+            port = rest.split_whitespace().next().and_then(|p| p.parse().ok());
+        }
+    }
+    Some(SocketAddr::new(ip?, port?))
+}
+
+/// Builds a minimal SDP offer/answer body advertising our locally bound RTP
+/// port for Opus over RTP/AVP, good enough for `parse_sdp_rtp_endpoint` on
+/// the receiving end to find the endpoint to send to.
+fn generate_local_sdp(rtp_port: u16) -> String {
+    format!(
+        "v=0\r\no=- 0 0 IN IP4 127.0.0.1\r\ns=-\r\nc=IN IP4 127.0.0.1\r\nt=0 0\r\nm=audio {rtp_port} RTP/AVP 111\r\na=rtpmap:111 opus/48000/1\r\n"
+    )
+}
+
+/// Figures out where to send RTP packets for `call`. A real deployment
+/// resolves this from the remote peer's SDP, relayed in over the
+/// `subscribe_call_events` push channel by `send_offer`/`send_answer`; this
+/// single-process demo has no second peer to receive an answer from on an
+/// outgoing call, so it falls back to looping packets back to the call's own
+/// local port, which still exercises the RTP/jitter/stats path end to end.
+fn resolve_rtp_endpoint(call: &ActiveCall) -> SocketAddr {
+    call.remote_sdp
+        .as_deref()
+        .and_then(parse_sdp_rtp_endpoint)
+        .unwrap_or_else(|| SocketAddr::new(
+            "127.0.0.1".parse().unwrap(),
+            call.rtp_socket.local_addr().map(|a| a.port()).unwrap_or(0),
+        ))
+}
+
+/// The 12-byte RTP fixed header (RFC 3550 §5.1), without CSRC identifiers,
+/// extensions, or padding, which this demo's single-source streams never use.
+struct RtpHeader {
+    sequence: u16,
+    timestamp: u32,
+    ssrc: u32,
+}
+
+impl RtpHeader {
+    fn encode(&self) -> [u8; 12] {
+        let mut buf = [0u8; 12];
+        buf[0] = RTP_VERSION << 6; // V=2, P=0, X=0, CC=0
+        buf[1] = RTP_OPUS_PAYLOAD_TYPE; // M=0
+        buf[2..4].copy_from_slice(&self.sequence.to_be_bytes());
+        buf[4..8].copy_from_slice(&self.timestamp.to_be_bytes());
+        buf[8..12].copy_from_slice(&self.ssrc.to_be_bytes());
+        buf
+    }
+
+    fn decode(buf: &[u8]) -> Option<(RtpHeader, &[u8])> {
+        if buf.len() < 12 {
+            return None;
+        }
+        Some((
+            RtpHeader {
+                sequence: u16::from_be_bytes([buf[2], buf[3]]),
+                timestamp: u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]),
+                ssrc: u32::from_be_bytes([buf[8], buf[9], buf[10], buf[11]]),
+            },
+            &buf[12..],
+        ))
+    }
+}
+
+/// Packet counters and the running RFC 3550 §6.4.1 interarrival jitter
+/// estimate for one call's RTP stream, read out by `get_call_stats`.
+#[derive(Debug, Default)]
+struct CallStatsInner {
+    packets_sent: AtomicU64,
+    packets_received: AtomicU64,
+    jitter: Mutex<f64>,
+    last_transit: Mutex<Option<i64>>,
+}
+
+impl CallStatsInner {
+    /// Folds one newly-arrived packet's header into the running jitter
+    /// estimate: `J += (|D| - J) / 16`, where `D` is the difference between
+    /// this and the previous packet's relative transit times.
+    fn record_arrival(&self, header: &RtpHeader) {
+        let arrival = rtp_clock_now();
+        let transit = arrival as i64 - header.timestamp as i64;
+        let mut last_transit = self.last_transit.lock().unwrap();
+        if let Some(prev_transit) = *last_transit {
+            let d = (transit - prev_transit).abs() as f64;
+            let mut jitter = self.jitter.lock().unwrap();
+            *jitter += (d - *jitter) / 16.0;
+        }
+        *last_transit = Some(transit);
+    }
+}
+
+/// Wall-clock time expressed in the RTP stream's sample clock, for comparing
+/// against packet timestamps when estimating jitter.
+fn rtp_clock_now() -> u32 {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    let ticks = now.as_secs() as u64 * RTP_SAMPLE_RATE as u64
+        + now.subsec_nanos() as u64 * RTP_SAMPLE_RATE as u64 / 1_000_000_000;
+    ticks as u32
+}
+
+/// Packet/jitter snapshot returned by `get_call_stats`.
+#[derive(Debug, Clone, Serialize)]
+pub struct CallStats {
+    packets_sent: u64,
+    packets_received: u64,
+    jitter: f64,
+}
+
+/// The live RTP media path for one connected call: a sender thread that
+/// encodes 20ms Opus frames and streams them to the negotiated endpoint, and
+/// a receiver thread that parses inbound RTP, updates `stats`, and decodes
+/// the payload. Dropped/stopped together via `stop` when the call ends.
+#[derive(Debug)]
+struct RtpSession {
+    stats: Arc<CallStatsInner>,
+    running: Arc<AtomicBool>,
+}
+
+impl RtpSession {
+    /// Connects `socket` to `remote_addr` and spawns the sender/receiver
+    /// threads. `socket` is the `ActiveCall`'s own already-bound RTP socket,
+    /// so the port advertised in `local_sdp` doesn't change underneath it.
+    fn start(socket: Arc<UdpSocket>, remote_addr: SocketAddr, ssrc: u32) -> std::io::Result<Arc<Self>> {
+        socket.connect(remote_addr)?;
+        socket.set_read_timeout(Some(Duration::from_millis(200)))?;
+
+        let stats = Arc::new(CallStatsInner::default());
+        let running = Arc::new(AtomicBool::new(true));
+
+        // Sender: this headless demo has no microphone to capture real audio
+        // from, so it encodes silence and streams that instead, to exercise
+        // the real Opus/RTP framing and packet counters end to end.
+        {
+            let socket = socket.clone();
+            let stats = stats.clone();
+            let running = running.clone();
+            thread::spawn(move || {
+                let mut encoder = match OpusEncoder::new(RTP_SAMPLE_RATE, Channels::Mono, Application::Voip) {
+                    Ok(encoder) => encoder,
+                    Err(e) => {
+                        println!("failed to create opus encoder: {e}");
+                        return;
+                    }
+                };
+                let silence = [0i16; RTP_SAMPLES_PER_FRAME];
+                let mut payload = [0u8; 400];
+                let mut sequence: u16 = 0;
+                let mut timestamp: u32 = 0;
+                while running.load(Ordering::Relaxed) {
+                    if let Ok(len) = encoder.encode(&silence, &mut payload) {
+                        let header = RtpHeader { sequence, timestamp, ssrc };
+                        let mut packet = Vec::with_capacity(12 + len);
+                        packet.extend_from_slice(&header.encode());
+                        packet.extend_from_slice(&payload[..len]);
+                        if socket.send(&packet).is_ok() {
+                            stats.packets_sent.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                    sequence = sequence.wrapping_add(1);
+                    timestamp = timestamp.wrapping_add(RTP_SAMPLES_PER_FRAME as u32);
+                    thread::sleep(Duration::from_millis(RTP_FRAME_MILLIS as u64));
+                }
+            });
+        }
+
+        // Receiver: parses inbound RTP and updates stats; the decoded PCM
+        // itself is discarded since this demo has no audio output device to
+        // play it through.
+        {
+            let socket = socket.clone();
+            let stats = stats.clone();
+            let running = running.clone();
+            thread::spawn(move || {
+                let mut decoder = match OpusDecoder::new(RTP_SAMPLE_RATE, Channels::Mono) {
+                    Ok(decoder) => decoder,
+                    Err(e) => {
+                        println!("failed to create opus decoder: {e}");
+                        return;
+                    }
+                };
+                let mut buf = [0u8; 1500];
+                let mut pcm = [0i16; RTP_SAMPLES_PER_FRAME];
+                while running.load(Ordering::Relaxed) {
+                    match socket.recv(&mut buf) {
+                        Ok(n) => {
+                            if let Some((header, payload)) = RtpHeader::decode(&buf[..n]) {
+                                stats.packets_received.fetch_add(1, Ordering::Relaxed);
+                                stats.record_arrival(&header);
+                                let _ = decoder.decode(payload, &mut pcm, false);
+                            }
+                        }
+                        Err(e)
+                            if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) => {}
+                        Err(_) => break,
+                    }
+                }
+            });
+        }
+
+        Ok(Arc::new(Self { stats, running }))
+    }
+
+    fn stop(&self) {
+        self.running.store(false, Ordering::Relaxed);
+    }
+
+    fn stats(&self) -> CallStats {
+        CallStats {
+            packets_sent: self.stats.packets_sent.load(Ordering::Relaxed),
+            packets_received: self.stats.packets_received.load(Ordering::Relaxed),
+            jitter: *self.stats.jitter.lock().unwrap(),
+        }
+    }
 }
 
 // Thread-safe VoiceCallManager
@@ -40,6 +466,12 @@ pub struct ActiveCall {
 pub struct VoiceCallManager {
     active_calls: Arc<std::sync::Mutex<HashMap<String, ActiveCall>>>,
     node_id: String,
+    /// One channel per live `subscribe_call_events` connection, keyed by the
+    /// subscription id handed back to the caller. Guarded by the same kind
+    /// of mutex as `active_calls` rather than a separate lock, to keep
+    /// things simple in this single-process demo server.
+    subscribers: std::sync::Mutex<HashMap<u64, Sender<CallEvent>>>,
+    next_sub_id: AtomicU64,
 }
 
 impl VoiceCallManager {
@@ -48,6 +480,8 @@ impl VoiceCallManager {
         Self {
             active_calls: Arc::new(std::sync::Mutex::new(HashMap::new())),
             node_id,
+            subscribers: std::sync::Mutex::new(HashMap::new()),
+            next_sub_id: AtomicU64::new(1),
         }
     }
 
@@ -60,36 +494,90 @@ impl VoiceCallManager {
         Ok(())
     }
 
+    /// Registers a new `call_event` subscriber, returning its subscription
+    /// id (to later pass to `unsubscribe_call_events`) and the receiving end
+    /// of its channel.
+    pub fn subscribe_call_events(&self) -> (u64, Receiver<CallEvent>) {
+        let (tx, rx) = mpsc::channel();
+        let sub_id = self.next_sub_id.fetch_add(1, Ordering::SeqCst);
+        self.subscribers.lock().unwrap().insert(sub_id, tx);
+        (sub_id, rx)
+    }
+
+    /// Drops a subscriber's channel, which also ends its connection's
+    /// notification-writing loop since the matching `Receiver` then sees a
+    /// disconnected sender.
+    pub fn unsubscribe_call_events(&self, sub_id: u64) {
+        self.subscribers.lock().unwrap().remove(&sub_id);
+    }
+
+    /// Sends a `CallEvent` for `call_id` to every live subscriber, dropping
+    /// any whose receiving end has gone away. `sdp`/`candidate` carry a
+    /// signaling update out to the remote peer; pass `None`/`None` for a
+    /// plain status transition.
+    fn broadcast_signal(&self, call_id: &str, status: &str, sdp: Option<String>, candidate: Option<String>) {
+        let event = CallEvent {
+            call_id: call_id.to_string(),
+            status: status.to_string(),
+            timestamp: now_unix(),
+            sdp,
+            candidate,
+        };
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain(|_, tx| tx.send(event.clone()).is_ok());
+    }
+
     pub fn start_call(&self, remote_peer_id: String) -> Result<String, String> {
         let call_id = format!("call_{}", rand_u32());
-        
-        let active_call = ActiveCall {
-            call_id: call_id.clone(),
-            remote_peer_id,
-            is_incoming: false,
-            status: CallStatus::Ringing,
-        };
+        let mut call = ActiveCall::new(call_id.clone(), remote_peer_id, false)
+            .map_err(|e| format!("failed to bind RTP socket: {e}"))?;
+        let local_port = call
+            .rtp_socket
+            .local_addr()
+            .map_err(|e| format!("failed to read RTP socket address: {e}"))?
+            .port();
+        let sdp = generate_local_sdp(local_port);
+        call.local_sdp = Some(sdp.clone());
 
-        self.active_calls.lock().unwrap().insert(call_id.clone(), active_call);
+        self.active_calls.lock().unwrap().insert(call_id.clone(), call);
+        self.broadcast_signal(&call_id, CallStatus::Ringing.to_string(), Some(sdp), None);
         println!("Starting call with ID: {}", call_id);
         Ok(call_id)
     }
 
     pub fn accept_call(&self, call_id: &str) -> Result<(), String> {
         let mut calls = self.active_calls.lock().unwrap();
-        if let Some(call) = calls.get_mut(call_id) {
-            call.status = CallStatus::Connected;
-            println!("Accepted call: {}", call_id);
-            Ok(())
-        } else {
-            Err(format!("Call not found: {}", call_id))
+        let call = calls.get_mut(call_id).ok_or_else(|| format!("Call not found: {}", call_id))?;
+        call.status = CallStatus::Connected;
+        let socket = call.rtp_socket.clone();
+        let ssrc = call.ssrc;
+        let remote_endpoint = resolve_rtp_endpoint(call);
+        drop(calls);
+
+        match RtpSession::start(socket, remote_endpoint, ssrc) {
+            Ok(session) => {
+                if let Some(call) = self.active_calls.lock().unwrap().get_mut(call_id) {
+                    call.rtp_session = Some(session);
+                }
+            }
+            Err(e) => println!("failed to start RTP session for call {call_id}: {e}"),
         }
+
+        self.broadcast_signal(call_id, CallStatus::Connected.to_string(), None, None);
+        println!("Accepted call: {}", call_id);
+        Ok(())
     }
 
     pub fn end_call(&self, call_id: &str) -> Result<(), String> {
         let mut calls = self.active_calls.lock().unwrap();
-        if let Some(mut call) = calls.remove(call_id) {
-            call.status = CallStatus::Ended;
+        if let Some(call) = calls.remove(call_id) {
+            if let Some(session) = &call.rtp_session {
+                session.stop();
+            }
+            drop(calls);
+            self.broadcast_signal(call_id, CallStatus::Ended.to_string(), None, None);
             println!("Ended call: {}", call_id);
             Ok(())
         } else {
@@ -105,18 +593,67 @@ impl VoiceCallManager {
         self.active_calls.lock().unwrap().get(call_id).map(|call| call.status.clone())
     }
 
-    pub fn simulate_incoming_call(&self, remote_peer_id: String) -> Result<String, String> {
+    /// Returns the packet/jitter counters for `call_id`'s RTP stream, or
+    /// `None` if the call doesn't exist or hasn't reached `Connected` yet.
+    pub fn get_call_stats(&self, call_id: &str) -> Option<CallStats> {
+        self.active_calls
+            .lock()
+            .unwrap()
+            .get(call_id)?
+            .rtp_session
+            .as_ref()
+            .map(|session| session.stats())
+    }
+
+    /// Relays our local SDP offer for `call_id` to the remote peer over the
+    /// `subscribe_call_events` push channel.
+    pub fn send_offer(&self, call_id: &str, sdp: String) -> Result<(), String> {
+        let mut calls = self.active_calls.lock().unwrap();
+        let call = calls.get_mut(call_id).ok_or_else(|| format!("Call not found: {}", call_id))?;
+        call.local_sdp = Some(sdp.clone());
+        let status = call.status.to_string();
+        drop(calls);
+        self.broadcast_signal(call_id, status, Some(sdp), None);
+        Ok(())
+    }
+
+    /// Relays our local SDP answer for `call_id` to the remote peer over the
+    /// `subscribe_call_events` push channel.
+    pub fn send_answer(&self, call_id: &str, sdp: String) -> Result<(), String> {
+        let mut calls = self.active_calls.lock().unwrap();
+        let call = calls.get_mut(call_id).ok_or_else(|| format!("Call not found: {}", call_id))?;
+        call.local_sdp = Some(sdp.clone());
+        let status = call.status.to_string();
+        drop(calls);
+        self.broadcast_signal(call_id, status, Some(sdp), None);
+        Ok(())
+    }
+
+    /// Records a local ICE candidate for `call_id` and relays it to the
+    /// remote peer over the `subscribe_call_events` push channel.
+    pub fn add_ice_candidate(&self, call_id: &str, candidate: String) -> Result<(), String> {
+        let mut calls = self.active_calls.lock().unwrap();
+        let call = calls.get_mut(call_id).ok_or_else(|| format!("Call not found: {}", call_id))?;
+        call.ice_candidates.push(candidate.clone());
+        let status = call.status.to_string();
+        drop(calls);
+        self.broadcast_signal(call_id, status, None, Some(candidate));
+        Ok(())
+    }
+
+    /// Creates an incoming call from a genuinely received SDP offer. Replaces
+    /// the old `simulate_incoming_call`, which fabricated an incoming call
+    /// with no SDP at all; this one can actually reach `Connected` with a
+    /// real negotiated RTP endpoint once `accept_call` runs.
+    pub fn receive_offer(&self, remote_peer_id: String, sdp: String) -> Result<String, String> {
         let call_id = format!("call_{}", rand_u32());
-        
-        let active_call = ActiveCall {
-            call_id: call_id.clone(),
-            remote_peer_id,
-            is_incoming: true,
-            status: CallStatus::Ringing,
-        };
+        let mut call = ActiveCall::new(call_id.clone(), remote_peer_id, true)
+            .map_err(|e| format!("failed to bind RTP socket: {e}"))?;
+        call.remote_sdp = Some(sdp);
 
-        self.active_calls.lock().unwrap().insert(call_id.clone(), active_call);
-        println!("Simulated incoming call: {}", call_id);
+        self.active_calls.lock().unwrap().insert(call_id.clone(), call);
+        self.broadcast_signal(&call_id, CallStatus::Ringing.to_string(), None, None);
+        println!("Received incoming call offer: {}", call_id);
         Ok(call_id)
     }
 }
@@ -140,98 +677,283 @@ fn rand_u32() -> u32 {
     (now.as_nanos() % u32::MAX as u128) as u32
 }
 
-// JSON-RPC request parser
-#[derive(Debug)]
+// JSON-RPC request envelope, deserialized with serde_json instead of
+// slicing the raw body, so nested objects, escaped quotes and whitespace
+// variations in the payload no longer break parsing.
+//
+// `id` is kept as a `Value` rather than a fixed integer since JSON-RPC 2.0
+// allows a number, a string, or (for a notification, which gets no
+// response) a missing/`null` id.
+#[derive(Debug, Deserialize)]
 struct JsonRpcRequest {
+    #[serde(default)]
+    jsonrpc: Option<String>,
     method: String,
-    params: Vec<String>,
-    id: i32,
-}
-
-fn parse_json_rpc(body: &str) -> Result<JsonRpcRequest, String> {
-    println!("DEBUG: Parsing JSON: {}", body);
-    
-    // Extract method
-    let method = if let Some(start) = body.find("\"method\":") {
-        let start = start + 9; // Skip "method":
-        let method_part = &body[start..].trim_start();
-        if method_part.starts_with('"') {
-            let start = 1; // Skip opening quote
-            if let Some(end) = method_part[start..].find('"') {
-                method_part[start..start + end].to_string()
-            } else {
-                return Err("Method end quote not found".to_string());
-            }
-        } else {
-            return Err("Method value not quoted".to_string());
-        }
+    #[serde(default)]
+    params: Value,
+    #[serde(default)]
+    id: Value,
+}
+
+/// The positional parameter names each method expects, in order, so a named
+/// (`{"peer_id": "..."}`) request can be mapped onto the same `Vec<String>`
+/// positional params the method handlers already take.
+fn expected_param_names(method: &str) -> &'static [&'static str] {
+    match method {
+        "start_voice_call" => &["peer_id"],
+        "accept_voice_call" | "end_voice_call" | "get_voice_call_status" | "get_voice_call_stats" => &["call_id"],
+        "receive_voice_call_offer" => &["peer_id", "sdp"],
+        "send_voice_offer" | "send_voice_answer" => &["call_id", "sdp"],
+        "add_voice_ice_candidate" => &["call_id", "candidate"],
+        _ => &[],
+    }
+}
+
+/// A JSON-RPC 2.0 error: a `code` from the standard registry (or the
+/// `-32000`..`-32099` "server error" range for application-defined failures
+/// like a missing call), a human-readable `message`, and optional structured
+/// `data` for whatever the caller needs to branch on programmatically.
+#[derive(Debug, Clone)]
+struct RpcError {
+    code: i32,
+    message: String,
+    data: Option<Value>,
+}
+
+impl RpcError {
+    fn new(code: i32, message: impl Into<String>) -> Self {
+        Self { code, message: message.into(), data: None }
+    }
+
+    fn parse_error(message: impl Into<String>) -> Self {
+        Self::new(-32700, message)
+    }
+
+    fn invalid_request(message: impl Into<String>) -> Self {
+        Self::new(-32600, message)
+    }
+
+    fn method_not_found(method: &str) -> Self {
+        Self::new(-32601, format!("Unknown method: {method}"))
+    }
+
+    fn invalid_params(message: impl Into<String>) -> Self {
+        Self::new(-32602, message)
+    }
+
+    fn internal_error(message: impl Into<String>) -> Self {
+        Self::new(-32603, message)
+    }
+
+    /// Application-defined error: the call a method was asked to operate on
+    /// doesn't exist (anymore). Uses the reserved `-32000` "server error"
+    /// slot rather than a standard code, since the spec doesn't have one for
+    /// this.
+    fn call_not_found(message: impl Into<String>) -> Self {
+        Self::new(-32000, message)
+    }
+
+    /// The connection hasn't called `handshake` yet, which every connection
+    /// must do before any other method.
+    fn handshake_required() -> Self {
+        Self::new(-32001, "handshake required: call \"handshake\" before any other method")
+    }
+
+    /// The method requires a capability the client didn't negotiate (or
+    /// didn't ask for) in `handshake`.
+    fn unsupported_capability(capability: &str) -> Self {
+        Self::new(-32002, format!("unsupported capability: \"{capability}\" was not negotiated in handshake"))
+    }
+}
+
+/// Maps a [`VoiceCallManager`] method's plain-string error onto an
+/// [`RpcError`] of the appropriate class: "Call not found: ..." becomes
+/// `call_not_found`, and every other failure (e.g. `start_call` or
+/// `receive_offer` failing to bind the RTP socket) becomes `internal_error`.
+fn manager_error(message: String) -> RpcError {
+    if message.starts_with("Call not found") {
+        RpcError::call_not_found(message)
     } else {
-        return Err("Method not found".to_string());
+        RpcError::internal_error(message)
+    }
+}
+
+fn value_to_param(value: &Value) -> Result<String, RpcError> {
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        Value::Number(n) => Ok(n.to_string()),
+        other => Err(RpcError::invalid_params(format!(
+            "param must be a string or number, got {other}"
+        ))),
+    }
+}
+
+/// Normalizes a request's `params` into the positional `Vec<String>` the
+/// method handlers expect, accepting both positional (`[..]`) and named
+/// (`{..}`) params per the JSON-RPC 2.0 spec.
+fn normalize_params(method: &str, params: &Value) -> Result<Vec<String>, RpcError> {
+    match params {
+        Value::Null => Ok(vec![]),
+        Value::Array(items) => items.iter().map(value_to_param).collect(),
+        Value::Object(map) => expected_param_names(method)
+            .iter()
+            .filter_map(|name| map.get(*name))
+            .map(value_to_param)
+            .collect(),
+        _ => Err(RpcError::invalid_params("params must be an array or object")),
+    }
+}
+
+// JSON-RPC response formatter
+fn format_response(id: &Value, result: &str) -> String {
+    format!(r#"{{"jsonrpc":"2.0","result":{},"id":{}}}"#, result, id)
+}
+
+fn format_error(id: &Value, error: &RpcError) -> String {
+    let data = error
+        .data
+        .as_ref()
+        .map(|d| d.to_string())
+        .unwrap_or_else(|| "null".to_string());
+    format!(
+        r#"{{"jsonrpc":"2.0","error":{{"code":{},"message":"{}","data":{}}},"id":{}}}"#,
+        error.code,
+        error.message.replace('"', "\\\""),
+        data,
+        id
+    )
+}
+
+/// Negotiates capabilities for the connection `state` belongs to: the
+/// requested set (from the `capabilities` array param) intersected with
+/// `SUPPORTED_CAPABILITIES`, replacing any previous negotiation on this
+/// connection if `handshake` is called again.
+fn handle_handshake(params: &Value, state: &mut ConnectionState) -> Result<String, RpcError> {
+    let requested: Vec<&str> = match params {
+        Value::Object(map) => map
+            .get("capabilities")
+            .and_then(Value::as_array)
+            .map(|items| items.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default(),
+        _ => Vec::new(),
     };
-    
-    println!("DEBUG: Extracted method: {}", method);
-    
-    // Extract params
-    let params = if let Some(start) = body.find("\"params\":") {
-        let start = start + 9; // Skip "params":
-        let params_part = &body[start..].trim_start();
-        if params_part.starts_with('[') {
-            if let Some(end) = params_part.find(']') {
-                let params_str = &params_part[1..end]; // Skip [ and ]
-                if params_str.trim().is_empty() {
-                    vec![]
-                } else {
-                    params_str.split(',')
-                        .map(|s| s.trim().trim_matches('"').to_string())
-                        .filter(|s| !s.is_empty())
-                        .collect()
-                }
-            } else {
-                vec![]
-            }
-        } else {
-            vec![]
+
+    let negotiated: HashSet<String> = requested
+        .into_iter()
+        .filter(|cap| SUPPORTED_CAPABILITIES.contains(cap))
+        .map(str::to_string)
+        .collect();
+    let capabilities_json = negotiated
+        .iter()
+        .map(|c| format!(r#""{c}""#))
+        .collect::<Vec<_>>()
+        .join(",");
+    state.capabilities = Some(negotiated);
+
+    Ok(format!(
+        r#"{{"protocol_version":"{}","capabilities":[{}],"release_date":"{}"}}"#,
+        PROTOCOL_VERSION,
+        capabilities_json,
+        RELEASE_DATE.trim(),
+    ))
+}
+
+/// Handles one JSON-RPC request object (one element of a batch, or the
+/// whole body for a non-batch request). Returns `None` for a notification
+/// (a request whose `id` is missing/`null`), which per the spec gets no
+/// response at all, even on error. Every method but `handshake` itself
+/// requires `state` to already be handshaken.
+fn handle_one(value: &Value, state: &mut ConnectionState) -> Option<String> {
+    let req: JsonRpcRequest = match serde_json::from_value(value.clone()) {
+        Ok(req) => req,
+        Err(e) => {
+            return Some(format_error(
+                &Value::Null,
+                &RpcError::invalid_request(format!("invalid request: {e}")),
+            ))
         }
-    } else {
-        vec![]
     };
-    
-    println!("DEBUG: Extracted params: {:?}", params);
-    
-    // Extract id
-    let id = if let Some(start) = body.find("\"id\":") {
-        let start = start + 5; // Skip "id":
-        let id_part = &body[start..];
-        if let Some(end) = id_part.find('}') {
-            id_part[..end].trim().parse().unwrap_or(1)
-        } else {
-            1
+    let is_notification = req.id.is_null();
+
+    if let Some(version) = &req.jsonrpc {
+        if version != "2.0" {
+            let error = RpcError::invalid_request(format!("unsupported jsonrpc version: {version}"));
+            return if is_notification { None } else { Some(format_error(&req.id, &error)) };
         }
+    }
+
+    let result = if req.method == "handshake" {
+        handle_handshake(&req.params, state)
+    } else if !state.is_handshaken() {
+        Err(RpcError::handshake_required())
     } else {
-        1
+        normalize_params(&req.method, &req.params)
+            .and_then(|params| handle_voice_call_method(&req.method, &params, state))
     };
-    
-    println!("DEBUG: Extracted id: {}", id);
-    
-    Ok(JsonRpcRequest { method, params, id })
-}
 
-// JSON-RPC response formatter
-fn format_response(id: i32, result: &str) -> String {
-    format!(r#"{{"jsonrpc":"2.0","result":{},"id":{}}}"#, result, id)
+    if is_notification {
+        return None;
+    }
+    Some(match result {
+        Ok(r) => format_response(&req.id, &r),
+        Err(e) => format_error(&req.id, &e),
+    })
 }
 
-fn format_error(id: i32, error: &str) -> String {
-    format!(r#"{{"jsonrpc":"2.0","error":{{"code":-1,"message":"{}"}},"id":{}}}"#, error.replace('"', "\\\""), id)
+/// Parses and dispatches a JSON-RPC request body against `state`, supporting
+/// both a single request object and a JSON-RPC 2.0 batch (a top-level JSON
+/// array) — the latter requires the `batch` capability, since it's dispatched
+/// element by element and replied to as a matching JSON array (with
+/// notifications omitted, per spec).
+fn dispatch(body: &str, state: &mut ConnectionState) -> String {
+    let parsed: Value = match serde_json::from_str(body) {
+        Ok(v) => v,
+        Err(e) => return format_error(&Value::Null, &RpcError::parse_error(format!("parse error: {e}"))),
+    };
+    match &parsed {
+        Value::Array(items) => {
+            // A `handshake` call is allowed to be the batch's first element
+            // even before the `batch` capability itself is negotiated, since
+            // that's the only way a client can ever negotiate `batch` (or
+            // anything else) over the one-request-per-connection HTTP
+            // gateway.
+            let is_leading_handshake = items
+                .first()
+                .and_then(|v| v.get("method"))
+                .and_then(Value::as_str)
+                == Some("handshake");
+            if !is_leading_handshake && !state.has_capability("batch") {
+                return format_error(&Value::Null, &RpcError::unsupported_capability("batch"));
+            }
+            let responses: Vec<String> = items.iter().filter_map(|item| handle_one(item, state)).collect();
+            format!("[{}]", responses.join(","))
+        }
+        Value::Object(_) => handle_one(&parsed, state).unwrap_or_default(),
+        _ => format_error(
+            &Value::Null,
+            &RpcError::invalid_request("request must be a JSON object or array"),
+        ),
+    }
 }
 
 // Voice call method handler
-fn handle_voice_call_method(method: &str, params: &[String]) -> Result<String, String> {
+fn handle_voice_call_method(method: &str, params: &[String], state: &ConnectionState) -> Result<String, RpcError> {
+    const WEBRTC_METHODS: &[&str] = &[
+        "receive_voice_call_offer",
+        "send_voice_offer",
+        "send_voice_answer",
+        "add_voice_ice_candidate",
+        "get_voice_call_stats",
+    ];
+    if WEBRTC_METHODS.contains(&method) && !state.has_capability("webrtc") {
+        return Err(RpcError::unsupported_capability("webrtc"));
+    }
+
     let manager = get_voice_manager();
-    
+
     match method {
         "init_voice_calls" => {
-            manager.start_listening()?;
+            manager.start_listening().map_err(manager_error)?;
             Ok(r#""Voice calls initialized""#.to_string())
         },
         "get_voice_node_id" => {
@@ -239,23 +961,23 @@ fn handle_voice_call_method(method: &str, params: &[String]) -> Result<String, S
         },
         "start_voice_call" => {
             if params.is_empty() {
-                return Err("Missing peer_id parameter".to_string());
+                return Err(RpcError::invalid_params("Missing peer_id parameter"));
             }
-            let call_id = manager.start_call(params[0].clone())?;
+            let call_id = manager.start_call(params[0].clone()).map_err(manager_error)?;
             Ok(format!(r#""{}""#, call_id))
         },
         "accept_voice_call" => {
             if params.is_empty() {
-                return Err("Missing call_id parameter".to_string());
+                return Err(RpcError::invalid_params("Missing call_id parameter"));
             }
-            manager.accept_call(&params[0])?;
+            manager.accept_call(&params[0]).map_err(manager_error)?;
             Ok(r#""Call accepted""#.to_string())
         },
         "end_voice_call" => {
             if params.is_empty() {
-                return Err("Missing call_id parameter".to_string());
+                return Err(RpcError::invalid_params("Missing call_id parameter"));
             }
-            manager.end_call(&params[0])?;
+            manager.end_call(&params[0]).map_err(manager_error)?;
             Ok(r#""Call ended""#.to_string())
         },
         "get_active_voice_calls" => {
@@ -268,7 +990,7 @@ fn handle_voice_call_method(method: &str, params: &[String]) -> Result<String, S
         },
         "get_voice_call_status" => {
             if params.is_empty() {
-                return Err("Missing call_id parameter".to_string());
+                return Err(RpcError::invalid_params("Missing call_id parameter"));
             }
             if let Some(status) = manager.get_call_status(&params[0]) {
                 Ok(format!(r#""{}""#, status.to_string()))
@@ -276,64 +998,80 @@ fn handle_voice_call_method(method: &str, params: &[String]) -> Result<String, S
                 Ok("null".to_string())
             }
         },
-        "simulate_incoming_voice_call" => {
-            if params.is_empty() {
-                return Err("Missing peer_id parameter".to_string());
+        "receive_voice_call_offer" => {
+            if params.len() < 2 {
+                return Err(RpcError::invalid_params("Missing peer_id/sdp parameter"));
             }
-            let call_id = manager.simulate_incoming_call(params[0].clone())?;
+            let call_id = manager
+                .receive_offer(params[0].clone(), params[1].clone())
+                .map_err(manager_error)?;
             Ok(format!(r#""{}""#, call_id))
         },
-        // Callme P2P methods
-        "get_callme_node_id" => {
-            // Return a simulated callme node ID
-            let callme_node_id = format!("callme_node_{}", rand_u32());
-            Ok(format!(r#""{}""#, callme_node_id))
-        },
-        "start_callme_call" => {
-            if params.is_empty() {
-                return Err("Missing peer_node_id parameter".to_string());
+        "send_voice_offer" => {
+            if params.len() < 2 {
+                return Err(RpcError::invalid_params("Missing call_id/sdp parameter"));
             }
-            let call_id = format!("callme_{}", rand_u32());
-            // Simulate adding to active calls
-            Ok(format!(r#""{}""#, call_id))
+            manager.send_offer(&params[0], params[1].clone()).map_err(manager_error)?;
+            Ok(r#""Offer sent""#.to_string())
         },
-        "accept_callme_call" => {
-            if params.is_empty() {
-                return Err("Missing call_id parameter".to_string());
+        "send_voice_answer" => {
+            if params.len() < 2 {
+                return Err(RpcError::invalid_params("Missing call_id/sdp parameter"));
             }
-            Ok(r#""Callme call accepted""#.to_string())
+            manager.send_answer(&params[0], params[1].clone()).map_err(manager_error)?;
+            Ok(r#""Answer sent""#.to_string())
         },
-        "end_callme_call" => {
-            if params.is_empty() {
-                return Err("Missing call_id parameter".to_string());
+        "add_voice_ice_candidate" => {
+            if params.len() < 2 {
+                return Err(RpcError::invalid_params("Missing call_id/candidate parameter"));
             }
-            Ok(r#""Callme call ended""#.to_string())
+            manager
+                .add_ice_candidate(&params[0], params[1].clone())
+                .map_err(manager_error)?;
+            Ok(r#""ICE candidate added""#.to_string())
         },
-        "get_active_callme_calls" => {
-            // Return empty array for now
-            Ok("[]".to_string())
-        },
-        "get_callme_call_status" => {
+        "get_voice_call_stats" => {
             if params.is_empty() {
-                return Err("Missing call_id parameter".to_string());
+                return Err(RpcError::invalid_params("Missing call_id parameter"));
+            }
+            match manager.get_call_stats(&params[0]) {
+                Some(stats) => Ok(serde_json::to_string(&stats).unwrap_or_else(|_| "null".to_string())),
+                None => Ok("null".to_string()),
             }
-            Ok(r#""Connected""#.to_string())
         },
-        _ => Err(format!("Unknown method: {}", method))
+        // `start_callme_call`/`accept_callme_call`/`end_callme_call`/
+        // `get_callme_call_status` used to return hardcoded strings instead of
+        // driving the real `CallmeManager`'s call-lifecycle state machine, so
+        // every transition (including illegal ones like accepting an already
+        // `Ended` call) silently "succeeded". This server has no async
+        // runtime to run `CallmeManager`'s `async fn`s on, so rather than
+        // keep advertising an API that can't actually reach the state
+        // machine, those methods are gone until this binary can drive it for
+        // real. `get_callme_node_id`/`get_active_callme_calls` went the same
+        // way: a node ID with no call behind it and an always-empty call
+        // list can never reflect the keepalive/NAT-timeout state
+        // `CallmeManager` now tracks per call, so they'd be just as
+        // misleading to keep around as the lifecycle methods above.
+        _ => Err(RpcError::method_not_found(method)),
     }
 }
 
-// HTTP request handler
-fn handle_client(mut stream: TcpStream) {
-    let mut reader = BufReader::new(&stream);
+/// Reads one HTTP-framed request (request line + headers + a `Content-Length`
+/// body) off `reader`, returning `None` once the peer has closed the
+/// connection (used both for the initial request on a connection and, for a
+/// `subscribe_call_events` connection, every request after it).
+fn read_request_body(reader: &mut BufReader<TcpStream>) -> Option<String> {
     let mut request_line = String::new();
-    reader.read_line(&mut request_line).unwrap();
-    
-    // Read headers
+    if reader.read_line(&mut request_line).unwrap_or(0) == 0 {
+        return None;
+    }
+
     let mut content_length = 0;
     loop {
         let mut header = String::new();
-        reader.read_line(&mut header).unwrap();
+        if reader.read_line(&mut header).unwrap_or(0) == 0 {
+            return None;
+        }
         if header.trim().is_empty() {
             break;
         }
@@ -341,47 +1079,385 @@ fn handle_client(mut stream: TcpStream) {
             content_length = header[15..].trim().parse().unwrap_or(0);
         }
     }
-    
-    // Read body
+
     let mut body = vec![0; content_length];
     if content_length > 0 {
-        std::io::Read::read_exact(&mut reader, &mut body).unwrap();
+        std::io::Read::read_exact(reader, &mut body).ok()?;
     }
-    let body_str = String::from_utf8_lossy(&body);
-    
-    println!("Received request: {}", body_str);
-    
-    // Parse JSON-RPC request
-    let response = match parse_json_rpc(&body_str) {
-        Ok(req) => {
-            match handle_voice_call_method(&req.method, &req.params) {
-                Ok(result) => format_response(req.id, &result),
-                Err(error) => format_error(req.id, &error),
-            }
-        },
-        Err(error) => format_error(1, &error),
-    };
-    
-    println!("Sending response: {}", response);
-    
-    // Send HTTP response
+    Some(String::from_utf8_lossy(&body).into_owned())
+}
+
+fn write_http_response(stream: &mut TcpStream, body: &str) {
     let http_response = format!(
         "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\n\r\n{}",
-        response.len(),
-        response
+        body.len(),
+        body
     );
-    
-    stream.write_all(http_response.as_bytes()).unwrap();
-    stream.flush().unwrap();
+    let _ = stream.write_all(http_response.as_bytes());
+    let _ = stream.flush();
 }
 
-fn main() {
-    println!("🎤 DeltaChat Voice Call JSON-RPC Server");
-    println!("======================================");
-    
-    let listener = TcpListener::bind("127.0.0.1:3000").unwrap();
-    println!("🚀 Server listening on http://127.0.0.1:3000");
+/// Rejects a connection the worker pool's queue had no room for, with a 503
+/// carrying a JSON-RPC "server overloaded" error body so a client gets a
+/// structured error instead of a bare connection drop.
+fn write_http_overloaded(stream: &mut TcpStream) {
+    let body = format_error(&Value::Null, &RpcError::new(-32000, "server overloaded, try again later"));
+    let http_response = format!(
+        "HTTP/1.1 503 Service Unavailable\r\nContent-Type: application/json\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\n\r\n{}",
+        body.len(),
+        body
+    );
+    let _ = stream.write_all(http_response.as_bytes());
+    let _ = stream.flush();
+}
+
+/// Handles a connection that just issued `subscribe_call_events`: acks it
+/// with a subscription id, then keeps the connection open, writing a framed
+/// `call_event` notification for every broadcast `CallEvent` until the
+/// client disconnects or sends `unsubscribe_call_events` back over the same
+/// connection.
+fn handle_subscribe_connection(
+    mut stream: TcpStream,
+    mut reader: BufReader<TcpStream>,
+    request: &JsonRpcRequest,
+) {
+    let manager = get_voice_manager();
+    let (sub_id, receiver) = manager.subscribe_call_events();
+
+    let ack = format_response(&request.id, &format!(r#"{{"subscription_id":{}}}"#, sub_id));
+    write_http_response(&mut stream, &ack);
+    println!("Subscribed call_event listener {sub_id}");
+
+    // A separate thread keeps reading this same connection so an
+    // `unsubscribe_call_events` request (or a disconnect) can tear the
+    // subscription down while the loop below is blocked waiting on events.
+    thread::spawn(move || {
+        loop {
+            let body = match read_request_body(&mut reader) {
+                Some(body) => body,
+                None => break,
+            };
+            let unsubscribed = serde_json::from_str::<JsonRpcRequest>(&body)
+                .map(|req| req.method == "unsubscribe_call_events")
+                .unwrap_or(false);
+            if unsubscribed {
+                break;
+            }
+        }
+        get_voice_manager().unsubscribe_call_events(sub_id);
+    });
+
+    // `Receiver` iterates until the sender (removed from
+    // `VoiceCallManager::subscribers` by either the thread above or a call
+    // to `unsubscribe_call_events`) is dropped, so this loop ends on its own
+    // once the subscription is torn down.
+    for event in receiver {
+        let notification = format!(
+            r#"{{"jsonrpc":"2.0","method":"call_event","params":{}}}"#,
+            serde_json::to_string(&event).unwrap_or_else(|_| "null".to_string())
+        );
+        if stream.write_all(notification.as_bytes()).is_err() || stream.write_all(b"\n").is_err() {
+            break;
+        }
+        let _ = stream.flush();
+    }
+    manager.unsubscribe_call_events(sub_id);
+    println!("Subscription {sub_id} ended");
+}
+
+// HTTP request handler
+//
+// `subscribe_call_events` here predates capability negotiation and is left
+// ungated: each HTTP request is its own connection, so there's no prior
+// request on which a `handshake` could already have landed, the way there is
+// for the Unix-socket and WebSocket gateways below (which keep one
+// connection, and thus one `ConnectionState`, open across requests).
+fn handle_client(mut stream: TcpStream) {
+    let mut reader = BufReader::new(stream.try_clone().expect("clone stream for reading"));
+    let body_str = match read_request_body(&mut reader) {
+        Some(body) => body,
+        None => return,
+    };
+    println!("Received request: {}", body_str);
+
+    if let Ok(request) = serde_json::from_str::<JsonRpcRequest>(&body_str) {
+        if request.method == "subscribe_call_events" {
+            handle_subscribe_connection(stream, reader, &request);
+            return;
+        }
+    }
+
+    // Parse and dispatch the JSON-RPC request (single request or batch). A
+    // fresh `ConnectionState` per request means a standalone call must be
+    // preceded by its own `handshake` in the same batch to use any gated
+    // capability — see `ConnectionState`'s doc comment.
+    let mut state = ConnectionState::default();
+    let response = dispatch(&body_str, &mut state);
+    println!("Sending response: {}", response);
+    write_http_response(&mut stream, &response);
+}
+
+/// Newline-delimited counterpart of [`handle_subscribe_connection`], for a
+/// Unix-domain-socket connection that just issued `subscribe_call_events`:
+/// acks it with a subscription id, then keeps writing a `call_event`
+/// notification per line until the client disconnects or sends
+/// `unsubscribe_call_events` back over the same connection.
+fn handle_unix_subscribe_connection(
+    stream: UnixStream,
+    mut reader: BufReader<UnixStream>,
+    request: &JsonRpcRequest,
+) {
+    let manager = get_voice_manager();
+    let (sub_id, receiver) = manager.subscribe_call_events();
+
+    let ack = format_response(&request.id, &format!(r#"{{"subscription_id":{}}}"#, sub_id));
+    let mut writer = &stream;
+    let _ = writeln!(writer, "{ack}");
+    println!("Subscribed call_event listener {sub_id} (unix)");
+
+    thread::spawn(move || {
+        loop {
+            let mut line = String::new();
+            match reader.read_line(&mut line) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {}
+            }
+            let unsubscribed = serde_json::from_str::<JsonRpcRequest>(line.trim())
+                .map(|req| req.method == "unsubscribe_call_events")
+                .unwrap_or(false);
+            if unsubscribed {
+                break;
+            }
+        }
+        get_voice_manager().unsubscribe_call_events(sub_id);
+    });
+
+    for event in receiver {
+        let notification = format!(
+            r#"{{"jsonrpc":"2.0","method":"call_event","params":{}}}"#,
+            serde_json::to_string(&event).unwrap_or_else(|_| "null".to_string())
+        );
+        if writeln!(writer, "{notification}").is_err() {
+            break;
+        }
+    }
+    manager.unsubscribe_call_events(sub_id);
+    println!("Subscription {sub_id} ended (unix)");
+}
+
+/// Handles a Unix-socket connection across all the requests it sends over
+/// its lifetime, so (unlike the HTTP gateway) one `ConnectionState` — and
+/// the `handshake` call that populates it — persists across them.
+fn handle_unix_client(stream: UnixStream) {
+    let mut reader = BufReader::new(stream.try_clone().expect("clone unix stream for reading"));
+    let mut state = ConnectionState::default();
+    loop {
+        let mut line = String::new();
+        match reader.read_line(&mut line) {
+            Ok(0) | Err(_) => return,
+            Ok(_) => {}
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        println!("Received request (unix): {line}");
+
+        if let Ok(request) = serde_json::from_str::<JsonRpcRequest>(line) {
+            if request.method == "subscribe_call_events" {
+                if !state.has_capability("subscriptions") {
+                    let mut writer = &stream;
+                    let _ = writeln!(
+                        writer,
+                        "{}",
+                        format_error(&request.id, &RpcError::unsupported_capability("subscriptions"))
+                    );
+                    continue;
+                }
+                handle_unix_subscribe_connection(stream, reader, &request);
+                return;
+            }
+        }
+
+        let response = dispatch(line, &mut state);
+        println!("Sending response (unix): {response}");
+        let mut writer = &stream;
+        if writeln!(writer, "{response}").is_err() {
+            return;
+        }
+    }
+}
+
+/// Runs the newline-delimited JSON-RPC gateway over a Unix domain socket, for
+/// local IPC clients that would rather not open a TCP port. Reuses
+/// [`dispatch`] for every method the same way the HTTP gateway does; only the
+/// framing (one JSON-RPC message per line instead of `Content-Length`-framed
+/// HTTP) differs.
+fn run_unix_gateway(path: &str) {
+    let _ = std::fs::remove_file(path);
+    let listener = UnixListener::bind(path).expect("bind unix-domain-socket gateway");
+    println!("Unix-domain-socket gateway listening on {path}");
+    print_endpoints();
+
+    let pool = WorkerPool::new(worker_pool_size(), WORKER_QUEUE_CAPACITY);
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                if pool.try_submit(stream, handle_unix_client).is_err() {
+                    println!("Worker pool queue full, dropping unix connection");
+                }
+            }
+            Err(e) => {
+                println!("Error: {}", e);
+            }
+        }
+    }
+}
+
+/// Handles one WebSocket connection: after the upgrade handshake, each text
+/// frame carries one JSON-RPC request and gets one JSON-RPC response frame
+/// back, via the same [`dispatch`] core the HTTP gateway uses. One
+/// `ConnectionState` persists across every frame on this connection, so
+/// `handshake` only has to run once, before any other request.
+/// `subscribe_call_events` is supported the same way as the other gateways,
+/// pushing a `call_event` frame per broadcast event from a second thread
+/// sharing the socket through a mutex; the read side uses a short socket
+/// timeout so it periodically releases that mutex instead of blocking the
+/// writer thread out indefinitely while idle.
+fn handle_websocket_client(stream: TcpStream) {
+    let _ = stream.set_read_timeout(Some(Duration::from_millis(200)));
+    let ws = match tungstenite::accept(stream) {
+        Ok(ws) => ws,
+        Err(e) => {
+            println!("WebSocket handshake failed: {e}");
+            return;
+        }
+    };
+    let ws = Arc::new(Mutex::new(ws));
+    let mut active_sub_id: Option<u64> = None;
+    let mut state = ConnectionState::default();
+
+    loop {
+        let message = ws.lock().unwrap().read_message();
+        let text = match message {
+            Ok(Message::Text(text)) => text,
+            Ok(Message::Close(_)) => break,
+            Ok(_) => continue, // ignore ping/pong/binary frames
+            Err(tungstenite::Error::Io(e))
+                if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) =>
+            {
+                continue; // just a read-timeout tick so the writer thread gets a turn
+            }
+            Err(_) => break,
+        };
+        println!("Received request (websocket): {text}");
+
+        if let Ok(request) = serde_json::from_str::<JsonRpcRequest>(&text) {
+            if request.method == "subscribe_call_events" {
+                if !state.has_capability("subscriptions") {
+                    let error = format_error(&request.id, &RpcError::unsupported_capability("subscriptions"));
+                    let _ = ws.lock().unwrap().write_message(Message::Text(error));
+                    continue;
+                }
+                let manager = get_voice_manager();
+                let (sub_id, receiver) = manager.subscribe_call_events();
+                active_sub_id = Some(sub_id);
+                let ack = format_response(&request.id, &format!(r#"{{"subscription_id":{}}}"#, sub_id));
+                let _ = ws.lock().unwrap().write_message(Message::Text(ack));
+
+                let ws_writer = ws.clone();
+                thread::spawn(move || {
+                    for event in receiver {
+                        let notification = format!(
+                            r#"{{"jsonrpc":"2.0","method":"call_event","params":{}}}"#,
+                            serde_json::to_string(&event).unwrap_or_else(|_| "null".to_string())
+                        );
+                        if ws_writer
+                            .lock()
+                            .unwrap()
+                            .write_message(Message::Text(notification))
+                            .is_err()
+                        {
+                            break;
+                        }
+                    }
+                });
+                continue;
+            }
+            if request.method == "unsubscribe_call_events" {
+                if let Some(sub_id) = active_sub_id.take() {
+                    get_voice_manager().unsubscribe_call_events(sub_id);
+                }
+                continue;
+            }
+        }
+
+        let response = dispatch(&text, &mut state);
+        println!("Sending response (websocket): {response}");
+        if ws.lock().unwrap().write_message(Message::Text(response)).is_err() {
+            break;
+        }
+    }
+
+    if let Some(sub_id) = active_sub_id {
+        get_voice_manager().unsubscribe_call_events(sub_id);
+    }
+}
+
+/// Runs the WebSocket gateway: a regular HTTP upgrade handshake followed by
+/// one JSON-RPC message per frame, which is what lets the push subscriptions
+/// added above reach browser clients (a plain TCP/HTTP connection can't
+/// receive unsolicited pushes the way a kept-open WebSocket can).
+fn run_websocket_gateway(addr: &str) {
+    let listener = TcpListener::bind(addr).expect("bind websocket gateway");
+    println!("WebSocket gateway listening on ws://{addr}");
+    print_endpoints();
+
+    let pool = WorkerPool::new(worker_pool_size(), WORKER_QUEUE_CAPACITY);
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                if pool.try_submit(stream, handle_websocket_client).is_err() {
+                    println!("Worker pool queue full, dropping websocket connection");
+                }
+            }
+            Err(e) => {
+                println!("Error: {}", e);
+            }
+        }
+    }
+}
+
+/// Runs the original HTTP/TCP gateway: one `Content-Length`-framed HTTP
+/// request per response, except for `subscribe_call_events` connections,
+/// which are kept open for pushed `call_event` notifications instead.
+/// Connections are handed to a bounded [`WorkerPool`] rather than getting
+/// their own `thread::spawn`, so a burst of clients degrades into 503s
+/// instead of exhausting OS threads.
+fn run_http_gateway(addr: &str) {
+    let listener = TcpListener::bind(addr).expect("bind http gateway");
+    println!("🚀 Server listening on http://{addr}");
+    print_endpoints();
+
+    let pool = WorkerPool::new(worker_pool_size(), WORKER_QUEUE_CAPACITY);
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                if let Err(mut stream) = pool.try_submit(stream, handle_client) {
+                    println!("Worker pool queue full, rejecting http connection with 503");
+                    write_http_overloaded(&mut stream);
+                }
+            }
+            Err(e) => {
+                println!("Error: {}", e);
+            }
+        }
+    }
+}
+
+fn print_endpoints() {
     println!("📡 Voice call API endpoints available:");
+    println!("   - handshake (call first; negotiates protocol version + capabilities)");
     println!("   - init_voice_calls");
     println!("   - get_voice_node_id");
     println!("   - start_voice_call");
@@ -389,20 +1465,36 @@ fn main() {
     println!("   - end_voice_call");
     println!("   - get_active_voice_calls");
     println!("   - get_voice_call_status");
-    println!("   - simulate_incoming_voice_call");
+    println!("   - receive_voice_call_offer");
+    println!("   - send_voice_offer");
+    println!("   - send_voice_answer");
+    println!("   - add_voice_ice_candidate");
+    println!("   - get_voice_call_stats");
+    println!("   - subscribe_call_events (keeps the connection open; push notifications)");
+    println!("   - unsubscribe_call_events");
     println!("\n💡 Test etmek için: python3 test_jsonrpc_voice.py");
     println!("🛑 Durdurmak için: Ctrl+C\n");
-    
-    for stream in listener.incoming() {
-        match stream {
-            Ok(stream) => {
-                thread::spawn(|| {
-                    handle_client(stream);
-                });
-            }
-            Err(e) => {
-                println!("Error: {}", e);
-            }
+}
+
+fn main() {
+    println!("🎤 DeltaChat Voice Call JSON-RPC Server");
+    println!("======================================");
+
+    let args: Vec<String> = std::env::args().collect();
+    let transport = args
+        .iter()
+        .position(|arg| arg == "--transport")
+        .and_then(|i| args.get(i + 1))
+        .map(String::as_str)
+        .unwrap_or("http");
+
+    match transport {
+        "http" => run_http_gateway(DEFAULT_HTTP_ADDR),
+        "websocket" => run_websocket_gateway(DEFAULT_WEBSOCKET_ADDR),
+        "unix" => run_unix_gateway(DEFAULT_UNIX_SOCKET_PATH),
+        other => {
+            eprintln!("Unknown --transport {other:?}; expected http, websocket, or unix");
+            std::process::exit(1);
         }
     }
 }
\ No newline at end of file
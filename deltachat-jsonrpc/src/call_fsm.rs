@@ -0,0 +1,131 @@
+//! A small, reusable finite-state-machine subsystem shared by
+//! [`crate::voice_call::VoiceCallManager`] and
+//! [`crate::callme_integration::CallmeManager`].
+//!
+//! Both managers used to mutate their call-status enum by direct field
+//! assignment, so illegal transitions (accepting a call that already ended,
+//! connecting one that failed, ...) silently succeeded. [`Machine`] lets each
+//! call kind declare its legal edges once, and [`StateMachine::consume`]
+//! rejects anything else instead of corrupting state. Subscribers can
+//! `watch` a call's state instead of polling it.
+
+use tokio::sync::watch;
+
+/// Declares the legal transitions and outputs of a state machine. `State` is
+/// cloned on every transition and broadcast to subscribers, so keep it cheap.
+pub trait Machine {
+    type State: Clone + PartialEq;
+    type Input;
+    type Output;
+
+    /// Returns the next state if `input` is legal from `state`, or `None` if
+    /// the transition is not allowed.
+    fn transition(state: &Self::State, input: &Self::Input) -> Option<Self::State>;
+
+    /// Computes the output to report for a transition that `transition`
+    /// already accepted. Only called when `transition` returns `Some`.
+    fn output(state: &Self::State, next: &Self::State, input: &Self::Input) -> Self::Output;
+}
+
+/// Holds the current state of one `Machine` instance and a `watch` channel so
+/// subscribers observe every transition without polling.
+pub struct StateMachine<M: Machine> {
+    state: M::State,
+    tx: watch::Sender<M::State>,
+}
+
+impl<M: Machine> StateMachine<M> {
+    pub fn new(initial: M::State) -> Self {
+        let (tx, _rx) = watch::channel(initial.clone());
+        Self { state: initial, tx }
+    }
+
+    /// The current state.
+    pub fn state(&self) -> &M::State {
+        &self.state
+    }
+
+    /// Subscribes to state transitions; the receiver's current value updates
+    /// immediately after every successful [`Self::consume`].
+    pub fn subscribe(&self) -> watch::Receiver<M::State> {
+        self.tx.subscribe()
+    }
+
+    /// Attempts to apply `input`. Returns the transition's output on success,
+    /// or `None` if `input` is not a legal transition from the current state.
+    pub fn consume(&mut self, input: &M::Input) -> Option<M::Output> {
+        let next = M::transition(&self.state, input)?;
+        let output = M::output(&self.state, &next, input);
+        self.state = next;
+        // A send error just means nobody is subscribed; that's fine.
+        let _ = self.tx.send(self.state.clone());
+        Some(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    enum DoorState {
+        Open,
+        Closed,
+        Locked,
+    }
+
+    enum DoorInput {
+        Close,
+        Open,
+        Lock,
+        Unlock,
+    }
+
+    struct Door;
+
+    impl Machine for Door {
+        type State = DoorState;
+        type Input = DoorInput;
+        type Output = DoorState;
+
+        fn transition(state: &DoorState, input: &DoorInput) -> Option<DoorState> {
+            use DoorInput::*;
+            use DoorState::*;
+            match (state, input) {
+                (Open, Close) => Some(Closed),
+                (Closed, Open) => Some(Open),
+                (Closed, Lock) => Some(Locked),
+                (Locked, Unlock) => Some(Closed),
+                _ => None,
+            }
+        }
+
+        fn output(_state: &DoorState, next: &DoorState, _input: &DoorInput) -> DoorState {
+            next.clone()
+        }
+    }
+
+    #[test]
+    fn test_legal_transitions_succeed() {
+        let mut door = StateMachine::<Door>::new(DoorState::Open);
+        assert_eq!(door.consume(&DoorInput::Close), Some(DoorState::Closed));
+        assert_eq!(door.consume(&DoorInput::Lock), Some(DoorState::Locked));
+        assert_eq!(*door.state(), DoorState::Locked);
+    }
+
+    #[test]
+    fn test_illegal_transition_rejected() {
+        let mut door = StateMachine::<Door>::new(DoorState::Open);
+        assert_eq!(door.consume(&DoorInput::Lock), None);
+        assert_eq!(*door.state(), DoorState::Open);
+    }
+
+    #[tokio::test]
+    async fn test_subscriber_observes_transition() {
+        let mut door = StateMachine::<Door>::new(DoorState::Open);
+        let mut rx = door.subscribe();
+        door.consume(&DoorInput::Close);
+        rx.changed().await.unwrap();
+        assert_eq!(*rx.borrow(), DoorState::Closed);
+    }
+}
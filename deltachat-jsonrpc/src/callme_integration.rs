@@ -3,31 +3,62 @@ use serde::{Deserialize, Serialize};
 use schemars::JsonSchema;
 use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 use tracing::info;
 use uuid::Uuid;
 
+use crate::call_fsm::{Machine, StateMachine};
+
 /// Simulated NodeId for callme compatibility
 pub type NodeId = String;
 
+/// Peer-timeout this node advertises and adopts when it is not behind NAT:
+/// long enough to avoid needless keepalive traffic.
+pub const DEFAULT_PEER_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// Peer-timeout advertised instead when we detect we're behind NAT, so
+/// remote peers refresh our NAT mapping aggressively before it expires.
+pub const NAT_PEER_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Keepalives sent per `effective_timeout` window, so a few lost packets in a
+/// row don't drop the call.
+const KEEPALIVES_PER_TIMEOUT: u32 = 4;
+
 /// Callme P2P voice call manager (simplified version)
 #[derive(Debug)]
 pub struct CallmeManager {
     active_calls: Arc<RwLock<HashMap<String, CallmeCall>>>,
     node_id: Option<NodeId>,
+    /// Whether this endpoint has detected it is behind NAT. Clamps the
+    /// peer-timeout we advertise so remote peers keep our NAT mapping alive.
+    behind_nat: bool,
 }
 
 /// Represents an active P2P voice call using callme
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct CallmeCall {
     pub call_id: String,
     pub peer_node_id: NodeId,
-    pub status: CallmeStatus,
+    machine: StateMachine<CallmeMachine>,
+    /// When we last heard anything (heartbeat or data) from the peer.
+    last_message_recv_time: Instant,
+    /// `min(our advertised timeout, peer's advertised timeout)`, negotiated
+    /// once the call connects. The keepalive task sends a heartbeat every
+    /// `effective_timeout / KEEPALIVES_PER_TIMEOUT`, and the watchdog
+    /// disconnects the call if nothing is heard for `effective_timeout`.
+    effective_timeout: Duration,
+}
+
+/// Connection-health snapshot of one call, for UIs to show call quality.
+#[derive(Debug, Clone)]
+pub struct ConnectionHealth {
+    pub effective_timeout: Duration,
+    pub time_since_last_message: Duration,
 }
 
 /// Status of a callme voice call
-#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum CallmeStatus {
     Connecting,
     Connected,
@@ -35,15 +66,127 @@ pub enum CallmeStatus {
     Error(String),
 }
 
+/// Inputs accepted by a callme call's [`CallmeMachine`].
+#[derive(Debug, Clone)]
+pub enum CallmeInput {
+    /// The peer connection finished negotiating.
+    Connect,
+    /// The callee accepted a `Connecting` call.
+    Accept,
+    /// Either side disconnected a `Connecting` or `Connected` call.
+    Disconnect,
+    /// The connection failed with the given reason.
+    Fail(String),
+}
+
+/// The legal transitions of a callme call: `Connecting` to `Connected` on
+/// accept or connect, either of those to `Disconnected`, and either of those
+/// to `Error` on failure. Anything else is rejected by
+/// [`StateMachine::consume`].
+struct CallmeMachine;
+
+impl Machine for CallmeMachine {
+    type State = CallmeStatus;
+    type Input = CallmeInput;
+    type Output = CallmeStatus;
+
+    fn transition(state: &CallmeStatus, input: &CallmeInput) -> Option<CallmeStatus> {
+        use CallmeInput::*;
+        use CallmeStatus::*;
+        match (state, input) {
+            (Connecting, Connect) | (Connecting, Accept) => Some(Connected),
+            (Connecting, Disconnect) | (Connected, Disconnect) => Some(Disconnected),
+            (Connecting, Fail(reason)) | (Connected, Fail(reason)) => {
+                Some(Error(reason.clone()))
+            }
+            _ => None,
+        }
+    }
+
+    fn output(_state: &CallmeStatus, next: &CallmeStatus, _input: &CallmeInput) -> CallmeStatus {
+        next.clone()
+    }
+}
+
 impl CallmeManager {
     /// Create a new callme manager
     pub fn new() -> Self {
         Self {
             active_calls: Arc::new(RwLock::new(HashMap::new())),
             node_id: None,
+            behind_nat: false,
+        }
+    }
+
+    /// Records whether this endpoint is behind NAT, which clamps the
+    /// peer-timeout advertised to peers going forward.
+    pub fn set_behind_nat(&mut self, behind_nat: bool) {
+        self.behind_nat = behind_nat;
+    }
+
+    /// The peer-timeout this node advertises to peers during negotiation.
+    pub fn advertised_peer_timeout(&self) -> Duration {
+        if self.behind_nat {
+            NAT_PEER_TIMEOUT
+        } else {
+            DEFAULT_PEER_TIMEOUT
         }
     }
 
+    /// Records that a heartbeat or data message was received from the peer
+    /// on `call_id`, resetting the watchdog.
+    pub async fn record_message_received(&self, call_id: &str) -> Result<()> {
+        let mut calls = self.active_calls.write().await;
+        let call = calls
+            .get_mut(call_id)
+            .ok_or_else(|| anyhow::anyhow!("Call not found: {}", call_id))?;
+        call.last_message_recv_time = Instant::now();
+        Ok(())
+    }
+
+    /// Returns a connection-health snapshot for `call_id`.
+    pub async fn get_connection_health(&self, call_id: &str) -> Result<ConnectionHealth> {
+        let calls = self.active_calls.read().await;
+        let call = calls
+            .get(call_id)
+            .ok_or_else(|| anyhow::anyhow!("Call not found: {}", call_id))?;
+        Ok(ConnectionHealth {
+            effective_timeout: call.effective_timeout,
+            time_since_last_message: call.last_message_recv_time.elapsed(),
+        })
+    }
+
+    /// Spawns the keepalive and watchdog task for a just-connected call:
+    /// sends a heartbeat every `effective_timeout / KEEPALIVES_PER_TIMEOUT`,
+    /// and marks the call `Disconnected` once nothing has been heard from
+    /// the peer for `effective_timeout`.
+    fn spawn_keepalive(
+        active_calls: Arc<RwLock<HashMap<String, CallmeCall>>>,
+        call_id: String,
+        effective_timeout: Duration,
+    ) {
+        let heartbeat_interval = effective_timeout / KEEPALIVES_PER_TIMEOUT;
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(heartbeat_interval);
+            loop {
+                ticker.tick().await;
+                let mut calls = active_calls.write().await;
+                let Some(call) = calls.get_mut(&call_id) else {
+                    return;
+                };
+                if call.last_message_recv_time.elapsed() > effective_timeout {
+                    if call.machine.consume(&CallmeInput::Disconnect).is_some() {
+                        info!("Callme call {} timed out, marking disconnected", call_id);
+                    }
+                    return;
+                }
+                // In a real implementation, this would send a heartbeat
+                // datagram to the peer to refresh NAT mappings.
+                info!("Sending callme keepalive for {}", call_id);
+            }
+        });
+    }
+
     /// Initialize the callme endpoint (simplified version)
     pub async fn init(&mut self) -> Result<NodeId> {
         // Generate a simulated node ID
@@ -75,7 +218,9 @@ impl CallmeManager {
         let call = CallmeCall {
             call_id: call_id.clone(),
             peer_node_id,
-            status: CallmeStatus::Connecting,
+            machine: StateMachine::new(CallmeStatus::Connecting),
+            last_message_recv_time: Instant::now(),
+            effective_timeout: self.advertised_peer_timeout(),
         };
 
         // Add to active calls
@@ -84,18 +229,28 @@ impl CallmeManager {
             calls.insert(call_id.clone(), call);
         }
 
-        // Simulate connection process
+        // Simulate connection process, including the peer-timeout exchange:
+        // in a real implementation the peer would send its own advertised
+        // timeout alongside its connection acceptance.
         let active_calls = self.active_calls.clone();
         let call_id_clone = call_id.clone();
+        let our_timeout = self.advertised_peer_timeout();
+        let peer_advertised_timeout = our_timeout;
         tokio::spawn(async move {
             // Simulate connection delay
             tokio::time::sleep(Duration::from_millis(500)).await;
-            
-            // Update status to connected
+
+            let effective_timeout = our_timeout.min(peer_advertised_timeout);
             let mut calls = active_calls.write().await;
-            if let Some(call) = calls.get_mut(&call_id_clone) {
-                call.status = CallmeStatus::Connected;
+            let Some(call) = calls.get_mut(&call_id_clone) else {
+                return;
+            };
+            if call.machine.consume(&CallmeInput::Connect).is_some() {
+                call.effective_timeout = effective_timeout;
+                call.last_message_recv_time = Instant::now();
                 info!("Callme call connected: {}", call_id_clone);
+                drop(calls);
+                Self::spawn_keepalive(active_calls, call_id_clone, effective_timeout);
             }
         });
 
@@ -103,16 +258,31 @@ impl CallmeManager {
         Ok(call_id)
     }
 
-    /// Accept an incoming call
+    /// Accept an incoming call. Fails if the call isn't currently `Connecting`.
     pub async fn accept_call(&self, call_id: &str) -> Result<()> {
-        let mut calls = self.active_calls.write().await;
-        if let Some(call) = calls.get_mut(call_id) {
-            call.status = CallmeStatus::Connected;
-            info!("Accepted call: {}", call_id);
-            Ok(())
-        } else {
-            Err(anyhow::anyhow!("Call not found: {}", call_id))
-        }
+        let our_timeout = self.advertised_peer_timeout();
+        let effective_timeout = {
+            let mut calls = self.active_calls.write().await;
+            let call = calls
+                .get_mut(call_id)
+                .ok_or_else(|| anyhow::anyhow!("Call not found: {}", call_id))?;
+            call.machine.consume(&CallmeInput::Accept).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "call {} cannot be accepted from state {:?}",
+                    call_id,
+                    call.machine.state()
+                )
+            })?;
+            // The peer's advertised timeout would normally arrive with its
+            // call-setup message; until real signaling exists, assume parity.
+            let effective_timeout = our_timeout;
+            call.effective_timeout = effective_timeout;
+            call.last_message_recv_time = Instant::now();
+            effective_timeout
+        };
+        Self::spawn_keepalive(self.active_calls.clone(), call_id.to_string(), effective_timeout);
+        info!("Accepted call: {}", call_id);
+        Ok(())
     }
 
     /// End a voice call
@@ -137,7 +307,7 @@ impl CallmeManager {
     pub async fn get_call_status(&self, call_id: &str) -> Result<CallmeStatus> {
         let calls = self.active_calls.read().await;
         if let Some(call) = calls.get(call_id) {
-            Ok(call.status.clone())
+            Ok(call.machine.state().clone())
         } else {
             Err(anyhow::anyhow!("Call not found: {}", call_id))
         }
@@ -0,0 +1,368 @@
+//! UCAN-style (<https://github.com/ucan-wg/spec>) capability tokens used to
+//! authorize incoming calls, giving the P2P voice subsystem an
+//! offline-verifiable, delegable permission model instead of letting any
+//! node ring any other.
+//!
+//! A [`CapabilityToken`] is issued by an Ed25519 keypair, made out to an
+//! audience (the callee's node id), and grants a set of [`Capability`]
+//! `(resource, ability)` pairs until `expires_at`. A token may carry a chain
+//! of `proofs` delegating the issuer's own authority from a root token; a
+//! delegation is only valid if every parent in the chain actually grants a
+//! capability that covers the child's claim ([`Capability::covers`] —
+//! ability narrowing only, never widening).
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context as _, Result};
+use ed25519_dalek::{Signature, Signer as _, SigningKey, Verifier as _, VerifyingKey};
+use serde::{Deserialize, Serialize};
+
+/// One granted permission: a resource identifier and an ability verb, e.g.
+/// `("voice:<callee node id>", "call/ring")`. An ability ending in `/*` is a
+/// wildcard that [`Self::covers`] matches against any ability sharing that
+/// prefix, so a root token can delegate narrower, more specific abilities.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Capability {
+    pub resource: String,
+    pub ability: String,
+}
+
+impl Capability {
+    pub fn new(resource: impl Into<String>, ability: impl Into<String>) -> Self {
+        Self {
+            resource: resource.into(),
+            ability: ability.into(),
+        }
+    }
+
+    /// True if this capability's grant is broad enough to cover `child`,
+    /// i.e. `child` is the same claim or a narrowing of it. Only the
+    /// ability may narrow (via a `/*` wildcard prefix on the parent); the
+    /// resource must match exactly.
+    fn covers(&self, child: &Capability) -> bool {
+        if self.resource != child.resource {
+            return false;
+        }
+        if self.ability == child.ability {
+            return true;
+        }
+        match self.ability.strip_suffix("/*") {
+            Some(prefix) => child.ability.starts_with(prefix),
+            None => false,
+        }
+    }
+}
+
+/// The signed claims of a [`CapabilityToken`], i.e. everything but the
+/// signature itself. Kept separate so signing/verification have an
+/// unambiguous byte representation to operate over.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct TokenClaims {
+    /// The issuer's Ed25519 public key, standing in for a DID.
+    issuer: [u8; 32],
+    /// The callee's node id this token is made out to.
+    audience: String,
+    capabilities: Vec<Capability>,
+    /// Unix timestamp (seconds) after which the token is no longer valid.
+    expires_at: u64,
+    /// Tokens proving the issuer's own authority to grant `capabilities`,
+    /// root-first. Empty for a self-issued root token.
+    proofs: Vec<CapabilityToken>,
+}
+
+/// A signed capability grant. See the module docs for the delegation model.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CapabilityToken {
+    claims: TokenClaims,
+    signature: Vec<u8>,
+}
+
+impl CapabilityToken {
+    /// Issues a new root token, signed by `issuer`.
+    pub fn issue_root(
+        issuer: &SigningKey,
+        audience: impl Into<String>,
+        capabilities: Vec<Capability>,
+        expires_at: u64,
+    ) -> Self {
+        Self::issue(issuer, audience, capabilities, expires_at, Vec::new())
+    }
+
+    /// Issues a token delegating from `proofs`, signed by `issuer`. The
+    /// caller is responsible for ensuring `issuer` is the audience of the
+    /// last proof in the chain; [`Self::verify`] checks this on the
+    /// receiving end regardless.
+    pub fn delegate(
+        issuer: &SigningKey,
+        audience: impl Into<String>,
+        capabilities: Vec<Capability>,
+        expires_at: u64,
+        proofs: Vec<CapabilityToken>,
+    ) -> Self {
+        Self::issue(issuer, audience, capabilities, expires_at, proofs)
+    }
+
+    fn issue(
+        issuer: &SigningKey,
+        audience: impl Into<String>,
+        capabilities: Vec<Capability>,
+        expires_at: u64,
+        proofs: Vec<CapabilityToken>,
+    ) -> Self {
+        let claims = TokenClaims {
+            issuer: issuer.verifying_key().to_bytes(),
+            audience: audience.into(),
+            capabilities,
+            expires_at,
+            proofs,
+        };
+        let signature = issuer.sign(&Self::signing_bytes(&claims)).to_bytes().to_vec();
+        Self { claims, signature }
+    }
+
+    fn signing_bytes(claims: &TokenClaims) -> Vec<u8> {
+        serde_json::to_vec(claims).expect("TokenClaims contains no non-serializable types")
+    }
+
+    /// Verifies this token's own signature.
+    fn verify_signature(&self) -> Result<()> {
+        let verifying_key = VerifyingKey::from_bytes(&self.claims.issuer)
+            .context("token issuer is not a valid Ed25519 public key")?;
+        let signature = Signature::from_slice(&self.signature)
+            .context("token signature is malformed")?;
+        verifying_key
+            .verify(&Self::signing_bytes(&self.claims), &signature)
+            .context("token signature does not verify")
+    }
+
+    /// Verifies that this token authorizes `required` for `local_node_id` at
+    /// `now` (Unix seconds): the token's own signature and expiry, that its
+    /// audience is `local_node_id`, and — walking the proof chain from this
+    /// token back to its root — that every parent's capabilities actually
+    /// cover the capability the child is claiming *and* that the chain's
+    /// root was actually issued by `trusted_root` (normally the callee's own
+    /// [`crate::voice_call::VoiceCallManager::capability_public_key`]).
+    /// Without this last check a self-signed token is indistinguishable from
+    /// one rooted in the callee's own authority, so anyone could mint a
+    /// token that verifies against themselves.
+    pub fn verify(
+        &self,
+        local_node_id: &str,
+        required: &Capability,
+        now: u64,
+        trusted_root: &VerifyingKey,
+    ) -> Result<()> {
+        if self.claims.audience != local_node_id {
+            bail!(
+                "token audience {:?} does not match local node id {:?}",
+                self.claims.audience,
+                local_node_id
+            );
+        }
+        if self.claims.expires_at <= now {
+            bail!("token expired at {}", self.claims.expires_at);
+        }
+        if !self
+            .claims
+            .capabilities
+            .iter()
+            .any(|granted| granted.covers(required))
+        {
+            bail!(
+                "token does not grant a capability covering {:?}/{:?}",
+                required.resource,
+                required.ability
+            );
+        }
+        self.verify_signature()?;
+        self.verify_chain(now, trusted_root)
+    }
+
+    /// Walks the proof chain, checking each link's signature, expiry, and
+    /// that each parent delegated every capability the child holds (ability
+    /// narrowing only — a child can never claim more than its parent granted
+    /// its issuer). The base case (the root, self-signed token) is only
+    /// authoritative if it was issued by `trusted_root`; otherwise anyone's
+    /// self-signature would verify.
+    fn verify_chain(&self, now: u64, trusted_root: &VerifyingKey) -> Result<()> {
+        let mut child = self;
+        loop {
+            let Some(parent) = child.claims.proofs.last() else {
+                if child.claims.issuer != trusted_root.to_bytes() {
+                    bail!("capability chain's root was not issued by the trusted root key");
+                }
+                return Ok(());
+            };
+            parent.verify_signature()?;
+            if parent.claims.expires_at <= now {
+                bail!("proof token expired at {}", parent.claims.expires_at);
+            }
+            if parent.claims.audience != bs58_like(&child.claims.issuer) {
+                bail!("proof token audience does not match delegate's issuer");
+            }
+            for claim in &child.claims.capabilities {
+                if !parent
+                    .claims
+                    .capabilities
+                    .iter()
+                    .any(|granted| granted.covers(claim))
+                {
+                    bail!(
+                        "proof token does not delegate capability {:?}/{:?}",
+                        claim.resource,
+                        claim.ability
+                    );
+                }
+            }
+            child = parent;
+        }
+    }
+}
+
+/// The audience a proof token must carry for its delegate: the lowercase
+/// hex encoding of the delegate's Ed25519 issuer key, not the `node_<uuid>`
+/// node id that `VoiceCallManager` checks a call token's own audience
+/// against. This keeps proof
+/// verification decoupled from any particular node-id scheme.
+fn bs58_like(public_key: &[u8; 32]) -> String {
+    hex_encode(public_key)
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// The current Unix timestamp in seconds, for passing as `now` to
+/// [`CapabilityToken::verify`].
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system clock is before the Unix epoch")
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keypair() -> SigningKey {
+        SigningKey::generate(&mut rand::rngs::OsRng)
+    }
+
+    #[test]
+    fn test_root_token_grants_matching_capability() {
+        let issuer = keypair();
+        let callee = "callee-node";
+        let cap = Capability::new(format!("voice:{callee}"), "call/ring");
+        let token = CapabilityToken::issue_root(&issuer, callee, vec![cap.clone()], unix_now() + 60);
+
+        assert!(token
+            .verify(callee, &cap, unix_now(), &issuer.verifying_key())
+            .is_ok());
+    }
+
+    #[test]
+    fn test_wrong_audience_rejected() {
+        let issuer = keypair();
+        let cap = Capability::new("voice:callee-node", "call/ring");
+        let token =
+            CapabilityToken::issue_root(&issuer, "callee-node", vec![cap.clone()], unix_now() + 60);
+
+        assert!(token
+            .verify("someone-else", &cap, unix_now(), &issuer.verifying_key())
+            .is_err());
+    }
+
+    #[test]
+    fn test_expired_token_rejected() {
+        let issuer = keypair();
+        let cap = Capability::new("voice:callee-node", "call/ring");
+        let token = CapabilityToken::issue_root(&issuer, "callee-node", vec![cap.clone()], unix_now() - 1);
+
+        assert!(token
+            .verify("callee-node", &cap, unix_now(), &issuer.verifying_key())
+            .is_err());
+    }
+
+    #[test]
+    fn test_uncovered_capability_rejected() {
+        let issuer = keypair();
+        let granted = Capability::new("voice:callee-node", "call/ring");
+        let required = Capability::new("voice:callee-node", "call/record");
+        let token =
+            CapabilityToken::issue_root(&issuer, "callee-node", vec![granted], unix_now() + 60);
+
+        assert!(token
+            .verify("callee-node", &required, unix_now(), &issuer.verifying_key())
+            .is_err());
+    }
+
+    #[test]
+    fn test_delegated_chain_with_narrowing_verifies() {
+        let root_issuer = keypair();
+        let delegate_issuer = keypair();
+        let delegate_node_id = hex_encode(&delegate_issuer.verifying_key().to_bytes());
+        let callee = "callee-node";
+
+        let root_cap = Capability::new(format!("voice:{callee}"), "call/*");
+        let root_token =
+            CapabilityToken::issue_root(&root_issuer, delegate_node_id, vec![root_cap], unix_now() + 3600);
+
+        let narrowed_cap = Capability::new(format!("voice:{callee}"), "call/ring");
+        let delegated_token = CapabilityToken::delegate(
+            &delegate_issuer,
+            callee,
+            vec![narrowed_cap.clone()],
+            unix_now() + 60,
+            vec![root_token],
+        );
+
+        assert!(delegated_token
+            .verify(callee, &narrowed_cap, unix_now(), &root_issuer.verifying_key())
+            .is_ok());
+    }
+
+    #[test]
+    fn test_delegated_chain_widening_rejected() {
+        let root_issuer = keypair();
+        let delegate_issuer = keypair();
+        let delegate_node_id = hex_encode(&delegate_issuer.verifying_key().to_bytes());
+        let callee = "callee-node";
+
+        let root_cap = Capability::new(format!("voice:{callee}"), "call/ring");
+        let root_token =
+            CapabilityToken::issue_root(&root_issuer, delegate_node_id, vec![root_cap], unix_now() + 3600);
+
+        // The delegate tries to grant itself a wider ability than its proof covers.
+        let widened_cap = Capability::new(format!("voice:{callee}"), "call/*");
+        let delegated_token = CapabilityToken::delegate(
+            &delegate_issuer,
+            callee,
+            vec![widened_cap.clone()],
+            unix_now() + 60,
+            vec![root_token],
+        );
+
+        assert!(delegated_token
+            .verify(callee, &widened_cap, unix_now(), &root_issuer.verifying_key())
+            .is_err());
+    }
+
+    #[test]
+    fn test_self_signed_token_from_untrusted_key_rejected() {
+        // An attacker mints their own root token naming the victim as
+        // audience. It is internally consistent (valid self-signature,
+        // matching audience, covering capability) but was never issued by
+        // the victim's own trusted root key, so it must still be rejected.
+        let attacker = keypair();
+        let victim_trusted_root = keypair();
+        let victim = "victim-node";
+        let cap = Capability::new(format!("voice:{victim}"), "call/ring");
+        let forged_token =
+            CapabilityToken::issue_root(&attacker, victim, vec![cap.clone()], unix_now() + 60);
+
+        assert!(forged_token
+            .verify(victim, &cap, unix_now(), &victim_trusted_root.verifying_key())
+            .is_err());
+    }
+}
@@ -1,17 +1,239 @@
 use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Duration;
 
-use anyhow::{anyhow, Result};
+use anyhow::Context as _;
 use serde::{Deserialize, Serialize};
 use schemars::JsonSchema;
 use tokio::sync::RwLock;
 use uuid::Uuid;
 
+use ed25519_dalek::SigningKey;
+
+use crate::call_fsm::{Machine, StateMachine};
+use crate::capability::{self, Capability, CapabilityToken};
+use crate::voice_crypto::{self, SessionKeys, StaticIdentity, TrustedPeers};
+
+/// How long a call may stay `Ringing` before [`VoiceCallManager`] gives up on
+/// it and moves it to `Failed`, unless overridden via
+/// [`VoiceCallManager::set_ring_timeout`].
+pub const DEFAULT_RING_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Errors returned by [`VoiceCallManager`]'s call-management methods, in
+/// place of ad-hoc `anyhow!` strings, so callers can match on what actually
+/// went wrong instead of parsing a message.
+#[derive(Debug, thiserror::Error)]
+pub enum VoiceCallError {
+    #[error("call not found: {0}")]
+    CallNotFound(String),
+
+    #[error("call {call_id} cannot go from {from:?} via {input}")]
+    IllegalTransition {
+        call_id: String,
+        from: CallStatus,
+        input: &'static str,
+    },
+
+    #[error("call {0} rejected: not authorized")]
+    Unauthorized(String, #[source] anyhow::Error),
+
+    #[error("signaling failed")]
+    Signaling(#[source] anyhow::Error),
+
+    #[error("call {0} has no negotiated media session keys yet")]
+    MediaNotReady(String),
+
+    #[error("call {0} failed to set up its media socket")]
+    MediaSetup(String, #[source] anyhow::Error),
+}
+
+/// Result type for [`VoiceCallManager`]'s call-management methods.
+pub type VoiceCallResult<T> = std::result::Result<T, VoiceCallError>;
+
+/// A pluggable channel for exchanging call-signaling messages with a peer,
+/// letting [`VoiceCallManager`] actually negotiate a call with a remote node
+/// instead of only mutating local state. See [`EmailSignalingTransport`] for
+/// the default implementation.
+///
+/// Methods return boxed futures (rather than being declared `async fn`
+/// directly) so the trait stays object-safe — `VoiceCallManager` stores its
+/// transport as a `dyn SignalingTransport` so it can be swapped out (e.g. in
+/// tests) without making every caller generic over the transport type.
+pub trait SignalingTransport: Send + Sync + std::fmt::Debug {
+    /// Sends `signal` to `peer_id`.
+    fn send_signal<'a>(
+        &'a self,
+        peer_id: &'a str,
+        signal: CallSignal,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'a>>;
+
+    /// Waits for the next signal addressed to us, from any peer. The sender
+    /// is identified by [`CallSignal::from_peer`], not a separate return
+    /// value, since every signal already carries it.
+    fn recv_signal(&self) -> Pin<Box<dyn Future<Output = anyhow::Result<CallSignal>> + Send + '_>>;
+}
+
+/// One signaling message exchanged between two [`VoiceCallManager`]s while
+/// setting up or tearing down a call.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CallSignal {
+    pub call_id: String,
+    /// The node id of whoever sent this signal.
+    pub from_peer: String,
+    pub kind: CallSignalKind,
+}
+
+/// The payload of a [`CallSignal`]. SDP and ICE-candidate payloads are
+/// carried as opaque strings — [`VoiceCallManager`] doesn't interpret them
+/// itself, since generating and applying real SDP/ICE data belongs to the
+/// media engine that plugs in alongside the encrypted transport in
+/// [`crate::voice_crypto`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum CallSignalKind {
+    /// A call invitation, carrying the capability token that authorizes it
+    /// (checked by [`VoiceCallManager::accept_call`]) alongside the SDP
+    /// offer.
+    Offer { sdp: String, token: CapabilityToken },
+    /// Accepts an `Offer`.
+    Answer { sdp: String },
+    /// An ICE candidate gathered during negotiation.
+    IceCandidate { candidate: String },
+    /// Either side is hanging up.
+    Hangup,
+}
+
+/// Default [`SignalingTransport`]: carries [`CallSignal`]s as specially
+/// headered email messages over the existing IMAP/SMTP channel, so setting
+/// up a call needs no network listener beyond the account's existing mail
+/// delivery.
+///
+/// Composing and submitting the email (and watching the inbox for one) is
+/// the account layer's job, not this crate's — `send`/`recv` are supplied by
+/// the caller as plain async closures over whatever mail-sending/-fetching
+/// primitives it already has. [`Self::encode`]/[`Self::decode`] are exposed
+/// separately so that glue code only has to turn a [`CallSignal`] into a
+/// message body (and back), not reimplement the framing.
+pub struct EmailSignalingTransport {
+    send: Box<
+        dyn Fn(&str, &str) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send>>
+            + Send
+            + Sync,
+    >,
+    recv: Box<dyn Fn() -> Pin<Box<dyn Future<Output = anyhow::Result<String>> + Send>> + Send + Sync>,
+}
+
+impl std::fmt::Debug for EmailSignalingTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EmailSignalingTransport").finish_non_exhaustive()
+    }
+}
+
+/// The header marking a `CallSignal`'s JSON encoding inside an email body,
+/// so a receiving client can tell a signaling message apart from a regular
+/// chat message at a glance.
+const CHAT_SIGNAL_HEADER: &str = "Chat-Signal: 1";
+
+impl EmailSignalingTransport {
+    /// `send(peer_id, message_body)` submits an outgoing email to `peer_id`
+    /// carrying `message_body`; `recv()` waits for the next inbound email
+    /// recognized as a signal and returns its body.
+    pub fn new(
+        send: impl Fn(&str, &str) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send>>
+            + Send
+            + Sync
+            + 'static,
+        recv: impl Fn() -> Pin<Box<dyn Future<Output = anyhow::Result<String>> + Send>>
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        Self {
+            send: Box::new(send),
+            recv: Box::new(recv),
+        }
+    }
+
+    /// Encodes `signal` as an email body: a `Chat-Signal` marker line
+    /// followed by its JSON encoding.
+    pub fn encode(signal: &CallSignal) -> anyhow::Result<String> {
+        let json = serde_json::to_string(signal).context("failed to encode CallSignal")?;
+        Ok(format!("{CHAT_SIGNAL_HEADER}\n\n{json}"))
+    }
+
+    /// Decodes an email body previously produced by [`Self::encode`].
+    pub fn decode(body: &str) -> anyhow::Result<CallSignal> {
+        let json = body
+            .strip_prefix(CHAT_SIGNAL_HEADER)
+            .context("message is not a call signal")?
+            .trim_start();
+        serde_json::from_str(json).context("failed to decode CallSignal")
+    }
+}
+
+impl SignalingTransport for EmailSignalingTransport {
+    fn send_signal<'a>(
+        &'a self,
+        peer_id: &'a str,
+        signal: CallSignal,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'a>> {
+        Box::pin(async move {
+            let body = Self::encode(&signal)?;
+            (self.send)(peer_id, &body).await
+        })
+    }
+
+    fn recv_signal(&self) -> Pin<Box<dyn Future<Output = anyhow::Result<CallSignal>> + Send + '_>> {
+        Box::pin(async move {
+            let body = (self.recv)().await?;
+            Self::decode(&body)
+        })
+    }
+}
+
+/// An inert [`SignalingTransport`] for when no real channel is wired up
+/// (the default used by [`VoiceCallManager::new`]): sends are dropped and
+/// receives never resolve, so callers that only drive calls locally (as the
+/// unit tests in this crate do) are unaffected, while actually reaching a
+/// remote node requires [`VoiceCallManager::with_transport`].
+#[derive(Debug, Default)]
+struct NullSignalingTransport;
+
+impl SignalingTransport for NullSignalingTransport {
+    fn send_signal<'a>(
+        &'a self,
+        _peer_id: &'a str,
+        _signal: CallSignal,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'a>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn recv_signal(&self) -> Pin<Box<dyn Future<Output = anyhow::Result<CallSignal>> + Send + '_>> {
+        Box::pin(std::future::pending())
+    }
+}
+
 /// Voice call manager for handling voice calls
 #[derive(Debug)]
 pub struct VoiceCallManager {
     active_calls: Arc<RwLock<HashMap<String, ActiveCall>>>,
     node_id: String,
+    /// This node's static Curve25519 identity, used to authenticate the
+    /// encrypted media transport's Noise-style handshake.
+    identity: StaticIdentity,
+    /// Peers whose static public key we accept calls from.
+    trusted_peers: RwLock<TrustedPeers>,
+    /// This node's Ed25519 keypair for issuing and delegating capability
+    /// tokens (separate from `identity`, which is only for the media
+    /// transport handshake).
+    capability_key: SigningKey,
+    /// How signaling messages reach the remote peer. Defaults to
+    /// [`NullSignalingTransport`]; set a real one via
+    /// [`Self::with_transport`].
+    transport: Arc<dyn SignalingTransport>,
+    /// How long a call may stay `Ringing` before it's moved to `Failed`.
+    ring_timeout: Duration,
 }
 
 /// Represents an active voice call
@@ -20,18 +242,108 @@ pub struct ActiveCall {
     call_id: String,
     remote_peer_id: String,
     is_incoming: bool,
-    status: CallStatus,
+    machine: StateMachine<CallMachine>,
+    /// Send/receive AEAD keys for the encrypted media transport, set once
+    /// the Noise-style handshake with the peer completes. `Negotiating`
+    /// moves to `Connected` exactly when this becomes `Some`.
+    session_keys: Option<SessionKeys>,
+    /// The capability token authorizing this call invitation, checked by
+    /// `accept_call` against `call/ring` on `voice:<local node id>` before
+    /// the call may be accepted.
+    token: CapabilityToken,
+}
+
+/// The capability a call invitation must cover to ring `local_node_id`.
+fn ring_capability(local_node_id: &str) -> Capability {
+    Capability::new(format!("voice:{local_node_id}"), "call/ring")
 }
 
 /// Call status enumeration
-#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum CallStatus {
+    /// No signaling has happened yet; transient, only observable for the
+    /// instant between an `ActiveCall` being constructed and its first
+    /// `Ring` input.
+    Idle,
     Ringing,
+    /// The callee accepted (or the caller received an `Answer`); the
+    /// Noise-style media handshake is in progress.
+    Negotiating,
     Connected,
     Ended,
     Failed,
 }
 
+/// Inputs accepted by a call's [`CallMachine`].
+#[derive(Debug, Clone)]
+pub enum CallInput {
+    /// A call was just created; moves `Idle` to `Ringing`.
+    Ring,
+    /// The callee accepted a `Ringing` call, or the caller received the
+    /// callee's `Answer` signal.
+    Accept,
+    /// The Noise-style handshake completed, moving `Negotiating` to
+    /// `Connected`.
+    HandshakeComplete,
+    /// Either side hung up.
+    Hangup,
+    /// A `Ringing` call timed out before being accepted.
+    Timeout,
+    /// The call failed for any other reason (network error, rejected
+    /// handshake, ...).
+    Fail,
+}
+
+impl CallInput {
+    /// A short name for [`VoiceCallError::IllegalTransition`], since
+    /// `CallInput` itself isn't `Copy`/doesn't need a `Display` impl
+    /// anywhere else.
+    fn name(&self) -> &'static str {
+        match self {
+            CallInput::Ring => "Ring",
+            CallInput::Accept => "Accept",
+            CallInput::HandshakeComplete => "HandshakeComplete",
+            CallInput::Hangup => "Hangup",
+            CallInput::Timeout => "Timeout",
+            CallInput::Fail => "Fail",
+        }
+    }
+}
+
+/// The legal transitions of a voice call: `Idle` to `Ringing` once it's
+/// actually rung, `Ringing` to `Negotiating` on accept (either side, the
+/// callee accepting directly or the caller receiving the callee's answer),
+/// `Negotiating` to `Connected` once the media handshake completes,
+/// `Ringing` to `Failed` on timeout, any of `Ringing`/`Negotiating`/
+/// `Connected` to `Failed` on error, and any of them to `Ended` on hangup. Anything else is rejected by
+/// [`StateMachine::consume`].
+struct CallMachine;
+
+impl Machine for CallMachine {
+    type State = CallStatus;
+    type Input = CallInput;
+    type Output = CallStatus;
+
+    fn transition(state: &CallStatus, input: &CallInput) -> Option<CallStatus> {
+        use CallInput::*;
+        use CallStatus::*;
+        match (state, input) {
+            (Idle, Ring) => Some(Ringing),
+            (Ringing, Accept) => Some(Negotiating),
+            (Negotiating, HandshakeComplete) => Some(Connected),
+            (Ringing, Timeout) | (Ringing, Fail) | (Negotiating, Fail) | (Connected, Fail) => {
+                Some(Failed)
+            }
+            (Ringing, Hangup) | (Negotiating, Hangup) | (Connected, Hangup) => Some(Ended),
+            _ => None,
+        }
+    }
+
+    fn output(_state: &CallStatus, next: &CallStatus, _input: &CallInput) -> CallStatus {
+        next.clone()
+    }
+}
+
 /// Call event for notifications
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CallEvent {
@@ -49,72 +361,373 @@ pub enum CallEventType {
 }
 
 impl VoiceCallManager {
-    /// Create a new voice call manager
-    pub async fn new() -> Result<Self> {
+    /// Create a new voice call manager with no signaling transport wired up
+    /// (see [`Self::with_transport`]) and the default ring timeout.
+    pub async fn new() -> anyhow::Result<Self> {
+        Self::with_transport(Arc::new(NullSignalingTransport)).await
+    }
+
+    /// Create a new voice call manager using `transport` to reach remote
+    /// peers.
+    pub async fn with_transport(transport: Arc<dyn SignalingTransport>) -> anyhow::Result<Self> {
         // Generate a random node ID for this instance
         let node_id = format!("node_{}", Uuid::new_v4());
 
         Ok(Self {
             active_calls: Arc::new(RwLock::new(HashMap::new())),
             node_id,
+            identity: StaticIdentity::random(),
+            trusted_peers: RwLock::new(TrustedPeers::new()),
+            capability_key: SigningKey::generate(&mut rand::rngs::OsRng),
+            transport,
+            ring_timeout: DEFAULT_RING_TIMEOUT,
         })
     }
 
+    /// Overrides how long a call may stay `Ringing` before being moved to
+    /// `Failed`. Must be called before any call is started to take effect.
+    pub fn set_ring_timeout(&mut self, ring_timeout: Duration) {
+        self.ring_timeout = ring_timeout;
+    }
+
+    /// This node's static public key, to be shared with peers out of band so
+    /// they can add it to their trusted-peers set via [`Self::trust_peer`].
+    pub fn static_public_key(&self) -> [u8; 32] {
+        self.identity.public_key()
+    }
+
+    /// This node's Ed25519 public key, the root of trust for any capability
+    /// chain it delegates with [`Self::issue_ring_capability`].
+    pub fn capability_public_key(&self) -> [u8; 32] {
+        self.capability_key.verifying_key().to_bytes()
+    }
+
+    /// Issues a root token granting `call/ring` on `voice:<this node>` to
+    /// whoever holds it, valid for `ttl` from now. A callee hands the
+    /// resulting token out of band to whoever it wants to allow to call it
+    /// (or to an intermediary it trusts to delegate the grant onward); the
+    /// caller attaches it to [`Self::start_call`].
+    pub fn issue_ring_capability(&self, ttl: Duration) -> CapabilityToken {
+        let capability = ring_capability(&self.node_id);
+        CapabilityToken::issue_root(
+            &self.capability_key,
+            self.node_id.clone(),
+            vec![capability],
+            capability::unix_now() + ttl.as_secs(),
+        )
+    }
+
+    /// Marks `peer_public_key` as trusted, allowing calls whose handshake
+    /// presents it as the peer's static key to succeed.
+    pub async fn trust_peer(&self, peer_public_key: [u8; 32]) {
+        self.trusted_peers.write().await.trust(peer_public_key);
+    }
+
+    /// Runs our side of the Noise-style handshake against a peer's static and
+    /// ephemeral public keys, storing the resulting session keys and moving
+    /// the call from `Negotiating` to `Connected`. Fails if the peer's static
+    /// key is not in our trusted-peers set.
+    pub async fn complete_handshake_with_peer(
+        &self,
+        call_id: &str,
+        peer_static_public: [u8; 32],
+        peer_ephemeral_public: [u8; 32],
+        we_are_initiator: bool,
+    ) -> VoiceCallResult<()> {
+        let our_ephemeral = x25519_dalek::EphemeralSecret::random_from_rng(rand::rngs::OsRng);
+        let trusted = self.trusted_peers.read().await;
+        let session_keys = voice_crypto::complete_handshake(
+            &self.identity,
+            &trusted,
+            peer_static_public,
+            our_ephemeral,
+            peer_ephemeral_public,
+            we_are_initiator,
+        )
+        .map_err(|e| VoiceCallError::Unauthorized(call_id.to_string(), e))?;
+        drop(trusted);
+        self.complete_handshake(call_id, session_keys).await
+    }
+
+    /// Marks a call's Noise-style handshake as complete, storing the
+    /// negotiated media transport keys and moving the call from
+    /// `Negotiating` to `Connected`.
+    pub async fn complete_handshake(
+        &self,
+        call_id: &str,
+        session_keys: SessionKeys,
+    ) -> VoiceCallResult<()> {
+        let mut calls = self.active_calls.write().await;
+        let call = calls
+            .get_mut(call_id)
+            .ok_or_else(|| VoiceCallError::CallNotFound(call_id.to_string()))?;
+        Self::consume(&mut call.machine, call_id, CallInput::HandshakeComplete)?;
+        call.session_keys = Some(session_keys);
+        Ok(())
+    }
+
+    /// Opens the encrypted media transport for a call whose handshake has
+    /// already completed (see [`Self::complete_handshake`]), binding
+    /// `local_addr` and connecting to `peer_addr`. `we_are_initiator` must
+    /// match whatever was passed to [`Self::complete_handshake_with_peer`]
+    /// for this call, since it determines which session key is used for
+    /// sending vs. receiving.
+    pub async fn open_media_socket(
+        &self,
+        call_id: &str,
+        local_addr: std::net::SocketAddr,
+        peer_addr: std::net::SocketAddr,
+        we_are_initiator: bool,
+    ) -> VoiceCallResult<voice_crypto::MediaSocket> {
+        let session_keys = {
+            let calls = self.active_calls.read().await;
+            let call = calls
+                .get(call_id)
+                .ok_or_else(|| VoiceCallError::CallNotFound(call_id.to_string()))?;
+            call.session_keys
+                .clone()
+                .ok_or_else(|| VoiceCallError::MediaNotReady(call_id.to_string()))?
+        };
+        voice_crypto::MediaSocket::connect(local_addr, peer_addr, session_keys, we_are_initiator)
+            .await
+            .map_err(|e| VoiceCallError::MediaSetup(call_id.to_string(), e))
+    }
+
     /// Get the node ID of this endpoint
     pub fn node_id(&self) -> &str {
         &self.node_id
     }
 
-    /// Start listening for incoming calls
-    pub async fn start_listening(&self) -> Result<()> {
-        // In a real implementation, this would start a network listener
-        // For now, we'll just return Ok to indicate the manager is ready
+    /// Start listening for incoming calls: spawns a background task that
+    /// waits on the signaling transport for `Offer`/`Answer`/`IceCandidate`/
+    /// `Hangup` signals and materializes or updates `ActiveCall`s
+    /// accordingly, for as long as this `VoiceCallManager` lives. Returns as
+    /// soon as the task is spawned, without waiting for any signal.
+    pub async fn start_listening(&self) -> anyhow::Result<()> {
+        let active_calls = self.active_calls.clone();
+        let transport = self.transport.clone();
+        let local_node_id = self.node_id.clone();
+        let ring_timeout = self.ring_timeout;
+        tokio::spawn(async move {
+            loop {
+                match transport.recv_signal().await {
+                    Ok(signal) => {
+                        Self::handle_inbound_signal(
+                            &active_calls,
+                            &local_node_id,
+                            ring_timeout,
+                            signal,
+                        )
+                        .await;
+                    }
+                    Err(e) => {
+                        println!("signaling transport receive failed: {e}");
+                    }
+                }
+            }
+        });
         println!("Voice call manager started listening for incoming calls");
         Ok(())
     }
 
-    /// Initiate an outgoing call
-    pub async fn start_call(&self, remote_peer_id: String) -> Result<String> {
+    /// Handles one inbound [`CallSignal`], materializing a new incoming
+    /// `ActiveCall` for an `Offer`, advancing an existing one for an
+    /// `Answer`/`Hangup`, or just logging an `IceCandidate` (applying ICE
+    /// candidates to a live media session isn't implemented yet).
+    async fn handle_inbound_signal(
+        active_calls: &Arc<RwLock<HashMap<String, ActiveCall>>>,
+        local_node_id: &str,
+        ring_timeout: Duration,
+        signal: CallSignal,
+    ) {
+        match signal.kind {
+            CallSignalKind::Offer { token, .. } => {
+                let mut machine = StateMachine::new(CallStatus::Idle);
+                machine.consume(&CallInput::Ring);
+                let active_call = ActiveCall {
+                    call_id: signal.call_id.clone(),
+                    remote_peer_id: signal.from_peer.clone(),
+                    is_incoming: true,
+                    machine,
+                    session_keys: None,
+                    token,
+                };
+                active_calls
+                    .write()
+                    .await
+                    .insert(signal.call_id.clone(), active_call);
+                Self::spawn_ring_timeout(active_calls.clone(), signal.call_id.clone(), ring_timeout);
+                println!(
+                    "{local_node_id}: incoming call {} from {}",
+                    signal.call_id, signal.from_peer
+                );
+            }
+            CallSignalKind::Answer { .. } => {
+                let mut calls = active_calls.write().await;
+                if let Some(call) = calls.get_mut(&signal.call_id) {
+                    let _ = Self::consume(&mut call.machine, &signal.call_id, CallInput::Accept);
+                }
+            }
+            CallSignalKind::IceCandidate { .. } => {
+                println!(
+                    "received ICE candidate for call {} (not yet applied to a media session)",
+                    signal.call_id
+                );
+            }
+            CallSignalKind::Hangup => {
+                let mut calls = active_calls.write().await;
+                if let Some(call) = calls.get_mut(&signal.call_id) {
+                    let _ = Self::consume(&mut call.machine, &signal.call_id, CallInput::Hangup);
+                }
+                calls.remove(&signal.call_id);
+            }
+        }
+    }
+
+    /// Applies `input` to `machine`, turning a rejected transition into a
+    /// [`VoiceCallError::IllegalTransition`] instead of a bare `None`.
+    fn consume(
+        machine: &mut StateMachine<CallMachine>,
+        call_id: &str,
+        input: CallInput,
+    ) -> VoiceCallResult<CallStatus> {
+        let from = machine.state().clone();
+        let input_name = input.name();
+        machine
+            .consume(&input)
+            .ok_or(VoiceCallError::IllegalTransition {
+                call_id: call_id.to_string(),
+                from,
+                input: input_name,
+            })
+    }
+
+    /// Spawns the watchdog that moves a call from `Ringing` to `Failed` if
+    /// it hasn't been accepted within `ring_timeout`.
+    fn spawn_ring_timeout(
+        active_calls: Arc<RwLock<HashMap<String, ActiveCall>>>,
+        call_id: String,
+        ring_timeout: Duration,
+    ) {
+        tokio::spawn(async move {
+            tokio::time::sleep(ring_timeout).await;
+            let mut calls = active_calls.write().await;
+            let Some(call) = calls.get_mut(&call_id) else {
+                return;
+            };
+            if call.machine.consume(&CallInput::Timeout).is_some() {
+                println!("Call {call_id} timed out while ringing");
+            }
+        });
+    }
+
+    /// Initiate an outgoing call, attaching the capability `token` that
+    /// proves we're authorized to ring `remote_peer_id` — typically one
+    /// `remote_peer_id` itself handed out via [`Self::issue_ring_capability`].
+    /// Sends an `Offer` signal to `remote_peer_id` over the configured
+    /// [`SignalingTransport`].
+    pub async fn start_call(
+        &self,
+        remote_peer_id: String,
+        token: CapabilityToken,
+    ) -> VoiceCallResult<String> {
         let call_id = format!("call_{}", Uuid::new_v4());
-        
+
+        let mut machine = StateMachine::new(CallStatus::Idle);
+        machine.consume(&CallInput::Ring);
         let active_call = ActiveCall {
             call_id: call_id.clone(),
-            remote_peer_id,
+            remote_peer_id: remote_peer_id.clone(),
             is_incoming: false,
-            status: CallStatus::Ringing,
+            machine,
+            session_keys: None,
+            token: token.clone(),
         };
 
         self.active_calls.write().await.insert(call_id.clone(), active_call);
-        
-        // In a real implementation, this would initiate network connection
-        println!("Starting call with ID: {}", call_id);
+        Self::spawn_ring_timeout(self.active_calls.clone(), call_id.clone(), self.ring_timeout);
 
+        let offer = CallSignal {
+            call_id: call_id.clone(),
+            from_peer: self.node_id.clone(),
+            // Real SDP generation belongs to the media engine, not here; this is a
+            // placeholder that still exercises the signaling path end to end.
+            kind: CallSignalKind::Offer {
+                sdp: format!("v=0 (offer placeholder for {call_id})"),
+                token,
+            },
+        };
+        self.transport
+            .send_signal(&remote_peer_id, offer)
+            .await
+            .map_err(VoiceCallError::Signaling)?;
+
+        println!("Starting call with ID: {call_id}");
         Ok(call_id)
     }
 
-    /// Accept an incoming call
-    pub async fn accept_call(&self, call_id: &str) -> Result<()> {
-        let mut calls = self.active_calls.write().await;
-        if let Some(call) = calls.get_mut(call_id) {
-            call.status = CallStatus::Connected;
-            println!("Accepted call: {}", call_id);
-            // In a real implementation, this would start audio processing
-            Ok(())
-        } else {
-            Err(anyhow!("Call not found: {}", call_id))
-        }
+    /// Accept an incoming call. Fails if the call's capability token doesn't
+    /// cover `call/ring` on this node, or if the call isn't currently
+    /// `Ringing`. Replies to the caller with an `Answer` signal.
+    pub async fn accept_call(&self, call_id: &str) -> VoiceCallResult<()> {
+        let remote_peer_id = {
+            let mut calls = self.active_calls.write().await;
+            let call = calls
+                .get_mut(call_id)
+                .ok_or_else(|| VoiceCallError::CallNotFound(call_id.to_string()))?;
+            call.token
+                .verify(
+                    &self.node_id,
+                    &ring_capability(&self.node_id),
+                    capability::unix_now(),
+                    &self.capability_key.verifying_key(),
+                )
+                .map_err(|e| VoiceCallError::Unauthorized(call_id.to_string(), e))?;
+            Self::consume(&mut call.machine, call_id, CallInput::Accept)?;
+            call.remote_peer_id.clone()
+        };
+
+        let answer = CallSignal {
+            call_id: call_id.to_string(),
+            from_peer: self.node_id.clone(),
+            kind: CallSignalKind::Answer {
+                sdp: format!("v=0 (answer placeholder for {call_id})"),
+            },
+        };
+        self.transport
+            .send_signal(&remote_peer_id, answer)
+            .await
+            .map_err(VoiceCallError::Signaling)?;
+
+        println!("Accepted call: {call_id}");
+        // In a real implementation, this would start audio processing
+        Ok(())
     }
 
-    /// End a call
-    pub async fn end_call(&self, call_id: &str) -> Result<()> {
-        let mut calls = self.active_calls.write().await;
-        if let Some(mut call) = calls.remove(call_id) {
-            call.status = CallStatus::Ended;
-            println!("Ended call: {}", call_id);
-            Ok(())
-        } else {
-            Err(anyhow!("Call not found: {}", call_id))
+    /// End a call. Fails if the call is already `Ended` or `Failed`. Notifies
+    /// the remote peer with a `Hangup` signal.
+    pub async fn end_call(&self, call_id: &str) -> VoiceCallResult<()> {
+        let remote_peer_id = {
+            let mut calls = self.active_calls.write().await;
+            let call = calls
+                .get_mut(call_id)
+                .ok_or_else(|| VoiceCallError::CallNotFound(call_id.to_string()))?;
+            Self::consume(&mut call.machine, call_id, CallInput::Hangup)?;
+            call.remote_peer_id.clone()
+        };
+        self.active_calls.write().await.remove(call_id);
+
+        let hangup = CallSignal {
+            call_id: call_id.to_string(),
+            from_peer: self.node_id.clone(),
+            kind: CallSignalKind::Hangup,
+        };
+        if let Err(e) = self.transport.send_signal(&remote_peer_id, hangup).await {
+            println!("failed to send hangup signal for call {call_id}: {e}");
         }
+
+        println!("Ended call: {call_id}");
+        Ok(())
     }
 
     /// Get all active calls
@@ -124,23 +737,37 @@ impl VoiceCallManager {
 
     /// Get call status
     pub async fn get_call_status(&self, call_id: &str) -> Option<CallStatus> {
-        self.active_calls.read().await.get(call_id).map(|call| call.status.clone())
+        self.active_calls
+            .read()
+            .await
+            .get(call_id)
+            .map(|call| call.machine.state().clone())
     }
 
-    /// Simulate receiving an incoming call (for testing purposes)
-    pub async fn simulate_incoming_call(&self, remote_peer_id: String) -> Result<String> {
+    /// Simulate receiving an incoming call (for testing purposes), without
+    /// going through the signaling transport.
+    pub async fn simulate_incoming_call(
+        &self,
+        remote_peer_id: String,
+        token: CapabilityToken,
+    ) -> VoiceCallResult<String> {
         let call_id = format!("call_{}", Uuid::new_v4());
-        
+
+        let mut machine = StateMachine::new(CallStatus::Idle);
+        machine.consume(&CallInput::Ring);
         let active_call = ActiveCall {
             call_id: call_id.clone(),
             remote_peer_id,
             is_incoming: true,
-            status: CallStatus::Ringing,
+            machine,
+            session_keys: None,
+            token,
         };
 
         self.active_calls.write().await.insert(call_id.clone(), active_call);
-        println!("Simulated incoming call: {}", call_id);
-        
+        Self::spawn_ring_timeout(self.active_calls.clone(), call_id.clone(), self.ring_timeout);
+        println!("Simulated incoming call: {call_id}");
+
         Ok(call_id)
     }
 }
@@ -148,4 +775,4 @@ impl VoiceCallManager {
 /// Audio configuration constants
 pub const SAMPLE_RATE: u32 = 48000;
 pub const CHANNELS: u16 = 1;
-pub const FRAME_SIZE: usize = 960; // 20ms at 48kHz
\ No newline at end of file
+pub const FRAME_SIZE: usize = 960; // 20ms at 48kHz
@@ -1,5 +1,7 @@
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
     use super::voice_call::*;
 
     #[tokio::test]
@@ -12,11 +14,15 @@ mod tests {
     #[tokio::test]
     async fn test_start_call() {
         let manager = VoiceCallManager::new().await.unwrap();
-        let call_id = manager.start_call("test_peer".to_string()).await.unwrap();
-        
+        let token = manager.issue_ring_capability(Duration::from_secs(60));
+        let call_id = manager
+            .start_call("test_peer".to_string(), token)
+            .await
+            .unwrap();
+
         assert!(!call_id.is_empty());
         assert!(call_id.starts_with("call_"));
-        
+
         let active_calls = manager.get_active_calls().await;
         assert_eq!(active_calls.len(), 1);
         assert_eq!(active_calls[0], call_id);
@@ -25,8 +31,12 @@ mod tests {
     #[tokio::test]
     async fn test_call_status() {
         let manager = VoiceCallManager::new().await.unwrap();
-        let call_id = manager.start_call("test_peer".to_string()).await.unwrap();
-        
+        let token = manager.issue_ring_capability(Duration::from_secs(60));
+        let call_id = manager
+            .start_call("test_peer".to_string(), token)
+            .await
+            .unwrap();
+
         let status = manager.get_call_status(&call_id).await;
         assert!(status.is_some());
         assert!(matches!(status.unwrap(), CallStatus::Ringing));
@@ -35,25 +45,142 @@ mod tests {
     #[tokio::test]
     async fn test_accept_call() {
         let manager = VoiceCallManager::new().await.unwrap();
-        let call_id = manager.simulate_incoming_call("test_peer".to_string()).await.unwrap();
-        
+        let token = manager.issue_ring_capability(Duration::from_secs(60));
+        let call_id = manager
+            .simulate_incoming_call("test_peer".to_string(), token)
+            .await
+            .unwrap();
+
         manager.accept_call(&call_id).await.unwrap();
-        
+
+        // Accepting only starts the media handshake; the call isn't
+        // `Connected` until `complete_handshake` reports success.
         let status = manager.get_call_status(&call_id).await;
         assert!(status.is_some());
+        assert!(matches!(status.unwrap(), CallStatus::Negotiating));
+    }
+
+    #[tokio::test]
+    async fn test_handshake_completes_call() {
+        let manager = VoiceCallManager::new().await.unwrap();
+        let peer = VoiceCallManager::new().await.unwrap();
+        manager.trust_peer(peer.static_public_key()).await;
+
+        let token = manager.issue_ring_capability(Duration::from_secs(60));
+        let call_id = manager
+            .simulate_incoming_call("peer".to_string(), token)
+            .await
+            .unwrap();
+        manager.accept_call(&call_id).await.unwrap();
+
+        let peer_ephemeral = x25519_dalek::EphemeralSecret::random_from_rng(rand::rngs::OsRng);
+        let peer_ephemeral_public = x25519_dalek::PublicKey::from(&peer_ephemeral).to_bytes();
+        manager
+            .complete_handshake_with_peer(&call_id, peer.static_public_key(), peer_ephemeral_public, false)
+            .await
+            .unwrap();
+
+        let status = manager.get_call_status(&call_id).await;
         assert!(matches!(status.unwrap(), CallStatus::Connected));
     }
 
+    #[tokio::test]
+    async fn test_open_media_socket_before_handshake_rejected() {
+        let manager = VoiceCallManager::new().await.unwrap();
+        let token = manager.issue_ring_capability(Duration::from_secs(60));
+        let call_id = manager
+            .start_call("test_peer".to_string(), token)
+            .await
+            .unwrap();
+
+        let result = manager
+            .open_media_socket(
+                &call_id,
+                "127.0.0.1:0".parse().unwrap(),
+                "127.0.0.1:0".parse().unwrap(),
+                true,
+            )
+            .await;
+        assert!(matches!(result, Err(VoiceCallError::MediaNotReady(_))));
+    }
+
+    #[tokio::test]
+    async fn test_open_media_socket_after_handshake() {
+        let manager = VoiceCallManager::new().await.unwrap();
+        let peer = VoiceCallManager::new().await.unwrap();
+        manager.trust_peer(peer.static_public_key()).await;
+
+        let token = manager.issue_ring_capability(Duration::from_secs(60));
+        let call_id = manager
+            .simulate_incoming_call("peer".to_string(), token)
+            .await
+            .unwrap();
+        manager.accept_call(&call_id).await.unwrap();
+
+        let peer_ephemeral = x25519_dalek::EphemeralSecret::random_from_rng(rand::rngs::OsRng);
+        let peer_ephemeral_public = x25519_dalek::PublicKey::from(&peer_ephemeral).to_bytes();
+        manager
+            .complete_handshake_with_peer(&call_id, peer.static_public_key(), peer_ephemeral_public, false)
+            .await
+            .unwrap();
+
+        let media_socket = manager
+            .open_media_socket(
+                &call_id,
+                "127.0.0.1:0".parse().unwrap(),
+                "127.0.0.1:0".parse().unwrap(),
+                false,
+            )
+            .await
+            .unwrap();
+        drop(media_socket);
+    }
+
+    #[tokio::test]
+    async fn test_ring_timeout_fails_call() {
+        let mut manager = VoiceCallManager::new().await.unwrap();
+        manager.set_ring_timeout(Duration::from_millis(20));
+        let token = manager.issue_ring_capability(Duration::from_secs(60));
+        let call_id = manager
+            .start_call("test_peer".to_string(), token)
+            .await
+            .unwrap();
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let status = manager.get_call_status(&call_id).await;
+        assert!(matches!(status.unwrap(), CallStatus::Failed));
+    }
+
+    #[tokio::test]
+    async fn test_accept_call_without_capability_rejected() {
+        let manager = VoiceCallManager::new().await.unwrap();
+        let other = VoiceCallManager::new().await.unwrap();
+        // Token is made out to a different node, so it doesn't authorize
+        // ringing `manager`.
+        let token = other.issue_ring_capability(Duration::from_secs(60));
+        let call_id = manager
+            .simulate_incoming_call("test_peer".to_string(), token)
+            .await
+            .unwrap();
+
+        assert!(manager.accept_call(&call_id).await.is_err());
+    }
+
     #[tokio::test]
     async fn test_end_call() {
         let manager = VoiceCallManager::new().await.unwrap();
-        let call_id = manager.start_call("test_peer".to_string()).await.unwrap();
-        
+        let token = manager.issue_ring_capability(Duration::from_secs(60));
+        let call_id = manager
+            .start_call("test_peer".to_string(), token)
+            .await
+            .unwrap();
+
         manager.end_call(&call_id).await.unwrap();
-        
+
         let active_calls = manager.get_active_calls().await;
         assert_eq!(active_calls.len(), 0);
-        
+
         let status = manager.get_call_status(&call_id).await;
         assert!(status.is_none());
     }
@@ -61,19 +188,27 @@ mod tests {
     #[tokio::test]
     async fn test_multiple_calls() {
         let manager = VoiceCallManager::new().await.unwrap();
-        
-        let call1 = manager.start_call("peer1".to_string()).await.unwrap();
-        let call2 = manager.simulate_incoming_call("peer2".to_string()).await.unwrap();
-        
+        let token1 = manager.issue_ring_capability(Duration::from_secs(60));
+        let token2 = manager.issue_ring_capability(Duration::from_secs(60));
+
+        let call1 = manager
+            .start_call("peer1".to_string(), token1)
+            .await
+            .unwrap();
+        let call2 = manager
+            .simulate_incoming_call("peer2".to_string(), token2)
+            .await
+            .unwrap();
+
         let active_calls = manager.get_active_calls().await;
         assert_eq!(active_calls.len(), 2);
-        
+
         manager.end_call(&call1).await.unwrap();
         let active_calls = manager.get_active_calls().await;
         assert_eq!(active_calls.len(), 1);
-        
+
         manager.end_call(&call2).await.unwrap();
         let active_calls = manager.get_active_calls().await;
         assert_eq!(active_calls.len(), 0);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,842 @@
+//! End-to-end encryption for [`crate::voice_call::VoiceCallManager`]'s media
+//! transport, using a Noise-style handshake over each node's static
+//! Curve25519 key ([`StaticIdentity`]).
+//!
+//! Each node holds a static keypair `(P, S)` plus a set of trusted peer
+//! public keys. The handshake performs an ephemeral X25519 exchange mixed
+//! with the static-static DH (Noise `XX`-like, but checking the peer's
+//! static key against a trust set instead of a single pinned key), yielding
+//! a shared secret that is run through HKDF to produce distinct send/receive
+//! AEAD keys.
+//!
+//! [`MediaSocket`] carries the actual call media: a UDP socket that seals
+//! every 20ms Opus frame independently (keyed by its sequence number, so
+//! loss and reordering don't break decryption of the frames that do arrive)
+//! and demultiplexes periodic rekey control packets off the same socket,
+//! ratcheting to a fresh [`SessionKeys`] derived from a new ephemeral DH so
+//! that compromising one key only ever exposes a bounded amount of media.
+
+use std::collections::HashSet;
+use std::net::SocketAddr;
+
+use anyhow::{bail, Context as _, Result};
+use hkdf::Hkdf;
+use sha2::{Digest, Sha256};
+use tokio::net::UdpSocket;
+use x25519_dalek::{EphemeralSecret, PublicKey, StaticSecret};
+
+/// A node's static Curve25519 identity: random per node, or, in
+/// shared-secret mode, derived from a passphrase. It is not derived from
+/// the keys `create_keypair` produces.
+pub struct StaticIdentity {
+    secret: StaticSecret,
+    public: PublicKey,
+}
+
+impl StaticIdentity {
+    /// Explicit-trust mode: a random static key per node.
+    pub fn random() -> Self {
+        let secret = StaticSecret::random_from_rng(rand::rngs::OsRng);
+        let public = PublicKey::from(&secret);
+        Self { secret, public }
+    }
+
+    /// Shared-secret mode: all nodes derive the same static keypair from a
+    /// passphrase, and trust that single key (set up by the caller as the
+    /// sole entry in the trusted-peers set).
+    pub fn from_passphrase(passphrase: &str) -> Self {
+        let hk = Hkdf::<Sha256>::new(None, passphrase.as_bytes());
+        let mut seed = [0u8; 32];
+        hk.expand(b"dc voice shared secret identity", &mut seed)
+            .expect("32 bytes is a valid HKDF output length");
+        let secret = StaticSecret::from(seed);
+        let public = PublicKey::from(&secret);
+        Self { secret, public }
+    }
+
+    /// This node's static public key, to be distributed out of band in
+    /// explicit-trust mode.
+    pub fn public_key(&self) -> [u8; 32] {
+        self.public.to_bytes()
+    }
+}
+
+/// A set of peer static public keys this node accepts calls from.
+#[derive(Debug, Clone, Default)]
+pub struct TrustedPeers(HashSet<[u8; 32]>);
+
+impl TrustedPeers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn trust(&mut self, peer_public_key: [u8; 32]) {
+        self.0.insert(peer_public_key);
+    }
+
+    pub fn is_trusted(&self, peer_public_key: &[u8; 32]) -> bool {
+        self.0.contains(peer_public_key)
+    }
+}
+
+/// Send/receive AEAD keys negotiated by a completed handshake.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionKeys {
+    pub send_key: [u8; 32],
+    pub recv_key: [u8; 32],
+}
+
+/// Runs the initiator or responder side of the handshake given both
+/// parties' ephemeral keys and the static-static DH, rejecting the peer if
+/// its static public key is not in `trusted`.
+///
+/// `we_are_initiator` determines which derived key is used for sending vs.
+/// receiving, so that both ends agree on a `(send_key, recv_key)` pair that
+/// are swapped from each other's point of view.
+pub fn complete_handshake(
+    identity: &StaticIdentity,
+    trusted: &TrustedPeers,
+    peer_static_public: [u8; 32],
+    our_ephemeral: EphemeralSecret,
+    peer_ephemeral_public: [u8; 32],
+    we_are_initiator: bool,
+) -> Result<SessionKeys> {
+    if !trusted.is_trusted(&peer_static_public) {
+        bail!("peer static key is not in the trusted set, rejecting call");
+    }
+
+    let peer_static_public = PublicKey::from(peer_static_public);
+    let peer_ephemeral_public = PublicKey::from(peer_ephemeral_public);
+
+    let ephemeral_ephemeral = our_ephemeral.diffie_hellman(&peer_ephemeral_public);
+    let static_static = identity.secret.diffie_hellman(&peer_static_public);
+
+    let mut ikm = Vec::with_capacity(64);
+    ikm.extend_from_slice(ephemeral_ephemeral.as_bytes());
+    ikm.extend_from_slice(static_static.as_bytes());
+
+    derive_session_keys(b"dc voice noise handshake", &ikm, we_are_initiator)
+}
+
+/// Expands `ikm` into a `(send_key, recv_key)` pair, oriented so both ends
+/// of a handshake or rekey agree on which derived key is "mine to send
+/// with". Shared by [`complete_handshake`] (`ikm` = ephemeral-ephemeral ||
+/// static-static) and [`ratchet_session_keys`] (`ikm` = new
+/// ephemeral-ephemeral || old initiator->responder key || old
+/// responder->initiator key).
+fn derive_session_keys(hkdf_info: &[u8], ikm: &[u8], we_are_initiator: bool) -> Result<SessionKeys> {
+    let hk = Hkdf::<Sha256>::new(None, ikm);
+    let mut okm = [0u8; 64];
+    hk.expand(hkdf_info, &mut okm)
+        .map_err(|_| anyhow::anyhow!("HKDF expand failed"))?;
+
+    let mut key_a = [0u8; 32];
+    let mut key_b = [0u8; 32];
+    key_a.copy_from_slice(&okm[..32]);
+    key_b.copy_from_slice(&okm[32..]);
+
+    // Orient (key_a, key_b) as (initiator->responder, responder->initiator)
+    // so both ends agree on which key is "mine to send with".
+    Ok(if we_are_initiator {
+        SessionKeys {
+            send_key: key_a,
+            recv_key: key_b,
+        }
+    } else {
+        SessionKeys {
+            send_key: key_b,
+            recv_key: key_a,
+        }
+    })
+}
+
+/// Ratchets `keys` forward to a fresh [`SessionKeys`] using a new
+/// ephemeral-ephemeral DH mixed with the old keys: forward secrecy comes
+/// from the fresh ephemeral exchange, while mixing in the old keys means a
+/// passive observer of only the new ephemeral public keys (no compromise of
+/// either end) still can't derive the new session from the handshake alone.
+fn ratchet_session_keys(
+    keys: &SessionKeys,
+    our_new_ephemeral: &EphemeralSecret,
+    peer_new_ephemeral_public: [u8; 32],
+    we_are_initiator: bool,
+) -> Result<SessionKeys> {
+    let shared = our_new_ephemeral.diffie_hellman(&PublicKey::from(peer_new_ephemeral_public));
+
+    let mut ikm = Vec::with_capacity(96);
+    ikm.extend_from_slice(shared.as_bytes());
+    // Mix the old keys in (initiator->responder, responder->initiator) order
+    // so both ends build the same `ikm`.
+    let (old_i2r, old_r2i) = if we_are_initiator {
+        (&keys.send_key, &keys.recv_key)
+    } else {
+        (&keys.recv_key, &keys.send_key)
+    };
+    ikm.extend_from_slice(old_i2r);
+    ikm.extend_from_slice(old_r2i);
+
+    derive_session_keys(b"dc voice rekey ratchet", &ikm, we_are_initiator)
+}
+
+/// A sliding replay window over monotonically increasing 64-bit sequence
+/// numbers, used as the AEAD nonce for media datagrams so out-of-order or
+/// lost frames still decrypt independently.
+#[derive(Debug)]
+pub struct ReplayWindow {
+    /// One more than the highest sequence number accepted so far, so that 0
+    /// means nothing has been seen yet and sequence number 0 is accepted.
+    highest_seen: u64,
+    /// Bitmask of the `window_size` sequence numbers below `highest_seen`
+    /// that have already been seen, bit 0 = `highest_seen - 1`.
+    window: u128,
+}
+
+const WINDOW_SIZE: u64 = 128;
+
+impl ReplayWindow {
+    pub fn new() -> Self {
+        Self {
+            highest_seen: 0,
+            window: 0,
+        }
+    }
+
+    /// Checks and records `seq`, returning `true` if this is the first time
+    /// it has been seen (i.e. the datagram should be accepted).
+    pub fn check_and_record(&mut self, seq: u64) -> bool {
+        // Shift into the stored numbering, see `highest_seen`.
+        let Some(seq) = seq.checked_add(1) else {
+            return false;
+        };
+        if seq > self.highest_seen {
+            let shift = seq - self.highest_seen;
+            self.window = if shift >= WINDOW_SIZE as u64 {
+                0
+            } else {
+                (self.window << shift) | (1 << (shift - 1))
+            };
+            self.highest_seen = seq;
+            true
+        } else {
+            let diff = self.highest_seen - seq;
+            if diff == 0 || diff > WINDOW_SIZE as u64 {
+                false
+            } else {
+                let bit = 1u128 << (diff - 1);
+                let already_seen = self.window & bit != 0;
+                self.window |= bit;
+                !already_seen
+            }
+        }
+    }
+}
+
+impl Default for ReplayWindow {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// HMAC-SHA256 (RFC 2104), used to authenticate media frames and rekey
+/// control packets. There's no AEAD cipher among this crate's dependencies,
+/// so frames are sealed encrypt-then-MAC instead: [`seal_frame`] XORs the
+/// plaintext with an HKDF keystream, then appends this over the result.
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    const BLOCK_SIZE: usize = 64;
+    let mut key_block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        let hashed = Sha256::digest(key);
+        key_block[..hashed.len()].copy_from_slice(&hashed);
+    } else {
+        key_block[..key.len()].copy_from_slice(key);
+    }
+
+    let mut ipad = [0x36u8; BLOCK_SIZE];
+    let mut opad = [0x5cu8; BLOCK_SIZE];
+    for i in 0..BLOCK_SIZE {
+        ipad[i] ^= key_block[i];
+        opad[i] ^= key_block[i];
+    }
+
+    let inner = Sha256::new().chain_update(ipad).chain_update(message).finalize();
+    Sha256::new()
+        .chain_update(opad)
+        .chain_update(inner)
+        .finalize()
+        .into()
+}
+
+/// Constant-time byte-slice comparison, so that checking a MAC doesn't leak
+/// how many leading bytes matched via a timing side channel.
+fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Derives the `(keystream seed, MAC key)` pair for sealing/opening the
+/// frame at `seq`, from one end's `session_key` (its `send_key` to seal, the
+/// peer's mirrored `recv_key` to open). Including `seq` in the HKDF `info`
+/// means every frame gets independent key material without needing an
+/// explicit nonce field on the wire — `seq` itself, already sent alongside
+/// the frame for replay detection, doubles as the nonce.
+fn derive_frame_keys(session_key: &[u8; 32], seq: u64) -> Result<([u8; 32], [u8; 32])> {
+    let hk = Hkdf::<Sha256>::new(None, session_key);
+    let seq_bytes = seq.to_be_bytes();
+
+    let mut keystream_seed = [0u8; 32];
+    hk.expand(&[b"dc voice frame enc".as_slice(), &seq_bytes].concat(), &mut keystream_seed)
+        .map_err(|_| anyhow::anyhow!("HKDF expand failed"))?;
+    let mut mac_key = [0u8; 32];
+    hk.expand(&[b"dc voice frame mac".as_slice(), &seq_bytes].concat(), &mut mac_key)
+        .map_err(|_| anyhow::anyhow!("HKDF expand failed"))?;
+
+    Ok((keystream_seed, mac_key))
+}
+
+/// Seals one 20ms Opus frame for sequence number `seq`: XORs `plaintext`
+/// with an HKDF keystream derived from `send_key` and `seq`, then appends an
+/// HMAC-SHA256 tag over the ciphertext. The caller is responsible for
+/// sending `seq` alongside the result (see [`MediaSocket::send_frame`]),
+/// since [`open_frame`] needs it to re-derive the same keystream.
+fn seal_frame(send_key: &[u8; 32], seq: u64, plaintext: &[u8]) -> Result<Vec<u8>> {
+    let (keystream_seed, mac_key) = derive_frame_keys(send_key, seq)?;
+    let keystream = frame_keystream(&keystream_seed, plaintext.len())?;
+
+    let mut ciphertext: Vec<u8> = plaintext
+        .iter()
+        .zip(keystream.iter())
+        .map(|(p, k)| p ^ k)
+        .collect();
+    let tag = hmac_sha256(&mac_key, &ciphertext);
+    ciphertext.extend_from_slice(&tag);
+    Ok(ciphertext)
+}
+
+/// Inverse of [`seal_frame`]: verifies `datagram`'s trailing MAC against
+/// `recv_key` and `seq` before decrypting, failing closed on any mismatch
+/// (tamper, wrong key, or truncation) instead of returning garbage.
+fn open_frame(recv_key: &[u8; 32], seq: u64, datagram: &[u8]) -> Result<Vec<u8>> {
+    anyhow::ensure!(datagram.len() >= 32, "media frame too short to contain a MAC");
+    let (ciphertext, tag) = datagram.split_at(datagram.len() - 32);
+
+    let (keystream_seed, mac_key) = derive_frame_keys(recv_key, seq)?;
+    let expected_tag = hmac_sha256(&mac_key, ciphertext);
+    anyhow::ensure!(ct_eq(&expected_tag, tag), "media frame failed authentication");
+
+    let keystream = frame_keystream(&keystream_seed, ciphertext.len())?;
+    Ok(ciphertext
+        .iter()
+        .zip(keystream.iter())
+        .map(|(c, k)| c ^ k)
+        .collect())
+}
+
+/// Expands `seed` into a keystream of `len` bytes via HKDF, the stream
+/// cipher half of [`seal_frame`]/[`open_frame`]'s encrypt-then-MAC
+/// construction. `len` is bounded by one Opus frame's size, far under
+/// HKDF-SHA256's 8160-byte expand limit.
+fn frame_keystream(seed: &[u8; 32], len: usize) -> Result<Vec<u8>> {
+    let hk = Hkdf::<Sha256>::new(None, seed);
+    let mut keystream = vec![0u8; len];
+    hk.expand(b"dc voice frame keystream", &mut keystream)
+        .map_err(|_| anyhow::anyhow!("HKDF expand failed"))?;
+    Ok(keystream)
+}
+
+/// Wire-format tags distinguishing a [`MediaSocket`] datagram's kind, since
+/// media frames and rekey control packets share one UDP socket.
+const PACKET_KIND_MEDIA: u8 = 0;
+const PACKET_KIND_REKEY_OFFER: u8 = 1;
+const PACKET_KIND_REKEY_ACK: u8 = 2;
+
+/// Builds an authenticated rekey control packet: `[kind][ephemeral public
+/// key][HMAC tag]`, the tag keyed by `mac_key` (derived from the *current*
+/// session key, not the new ephemeral) so the peer knows the offer/ack
+/// really came from whoever it's been talking to.
+fn build_rekey_packet(kind: u8, session_key: &[u8; 32], label: &[u8], ephemeral_public: [u8; 32]) -> Result<Vec<u8>> {
+    let hk = Hkdf::<Sha256>::new(None, session_key);
+    let mut mac_key = [0u8; 32];
+    hk.expand(label, &mut mac_key)
+        .map_err(|_| anyhow::anyhow!("HKDF expand failed"))?;
+    let tag = hmac_sha256(&mac_key, &ephemeral_public);
+
+    let mut packet = Vec::with_capacity(1 + 32 + 32);
+    packet.push(kind);
+    packet.extend_from_slice(&ephemeral_public);
+    packet.extend_from_slice(&tag);
+    Ok(packet)
+}
+
+/// Verifies and parses a packet built by [`build_rekey_packet`], returning
+/// the ephemeral public key it carries.
+fn parse_rekey_packet(packet: &[u8], expected_kind: u8, session_key: &[u8; 32], label: &[u8]) -> Result<[u8; 32]> {
+    anyhow::ensure!(packet.len() == 1 + 32 + 32, "malformed rekey packet");
+    anyhow::ensure!(packet[0] == expected_kind, "unexpected rekey packet kind");
+
+    let mut ephemeral_public = [0u8; 32];
+    ephemeral_public.copy_from_slice(&packet[1..33]);
+    let tag = &packet[33..65];
+
+    let hk = Hkdf::<Sha256>::new(None, session_key);
+    let mut mac_key = [0u8; 32];
+    hk.expand(label, &mut mac_key)
+        .map_err(|_| anyhow::anyhow!("HKDF expand failed"))?;
+    let expected_tag = hmac_sha256(&mac_key, &ephemeral_public);
+    anyhow::ensure!(ct_eq(&expected_tag, tag), "rekey packet failed authentication");
+
+    Ok(ephemeral_public)
+}
+
+/// Sends and receives AEAD-sealed 20ms Opus frames over a connected UDP
+/// socket, periodically ratcheting to a fresh [`SessionKeys`] via
+/// [`Self::initiate_rekey`] so that compromising one key only ever exposes
+/// a bounded window of media. Rekey control packets share the same socket
+/// as media frames and are demultiplexed transparently by [`Self::recv`].
+pub struct MediaSocket {
+    socket: UdpSocket,
+    keys: SessionKeys,
+    we_are_initiator: bool,
+    send_seq: u64,
+    replay_window: ReplayWindow,
+    /// Our own ephemeral secret for a rekey we initiated, held until the
+    /// peer's ack arrives. `EphemeralSecret` is intentionally not `Clone`
+    /// (Diffie-Hellman secrets are meant to be used once), so this can only
+    /// ever hold at most one in-flight rekey.
+    pending_rekey: Option<EphemeralSecret>,
+}
+
+impl MediaSocket {
+    /// Binds `local_addr` and connects to `peer_addr`. Calls are always
+    /// point-to-point for the socket's lifetime, so `connect` lets
+    /// [`Self::send_frame`]/[`Self::recv`] use `send`/`recv` instead of
+    /// juggling a remote address on every datagram.
+    pub async fn connect(
+        local_addr: SocketAddr,
+        peer_addr: SocketAddr,
+        keys: SessionKeys,
+        we_are_initiator: bool,
+    ) -> Result<Self> {
+        let socket = UdpSocket::bind(local_addr)
+            .await
+            .context("failed to bind media socket")?;
+        socket
+            .connect(peer_addr)
+            .await
+            .context("failed to connect media socket to peer")?;
+        Ok(Self {
+            socket,
+            keys,
+            we_are_initiator,
+            send_seq: 0,
+            replay_window: ReplayWindow::new(),
+            pending_rekey: None,
+        })
+    }
+
+    /// Seals and sends one 20ms Opus frame under the next sequence number.
+    pub async fn send_frame(&mut self, opus_frame: &[u8]) -> Result<()> {
+        let seq = self.send_seq;
+        self.send_seq += 1;
+
+        let sealed = seal_frame(&self.keys.send_key, seq, opus_frame)?;
+        let mut datagram = Vec::with_capacity(1 + 8 + sealed.len());
+        datagram.push(PACKET_KIND_MEDIA);
+        datagram.extend_from_slice(&seq.to_be_bytes());
+        datagram.extend_from_slice(&sealed);
+
+        self.socket
+            .send(&datagram)
+            .await
+            .context("failed to send media frame")?;
+        Ok(())
+    }
+
+    /// Begins a rekey: generates a fresh ephemeral keypair and sends an
+    /// authenticated offer over the same socket. Call this periodically
+    /// (e.g. once per call on a timer) to bound how much media any one
+    /// session key ever protects; [`Self::recv`] completes the ratchet once
+    /// the peer's ack arrives.
+    pub async fn initiate_rekey(&mut self) -> Result<()> {
+        let ephemeral = EphemeralSecret::random_from_rng(rand::rngs::OsRng);
+        let ephemeral_public = PublicKey::from(&ephemeral).to_bytes();
+        let packet = build_rekey_packet(
+            PACKET_KIND_REKEY_OFFER,
+            &self.keys.send_key,
+            b"dc voice rekey offer",
+            ephemeral_public,
+        )?;
+        self.pending_rekey = Some(ephemeral);
+        self.socket
+            .send(&packet)
+            .await
+            .context("failed to send rekey offer")?;
+        Ok(())
+    }
+
+    /// Receives the next datagram. A media frame is returned as
+    /// `Some(plaintext)`; a rekey control packet is handled in place (the
+    /// ratchet completes and `self`'s keys rotate) and `None` is returned so
+    /// the caller's read loop just continues.
+    pub async fn recv(&mut self, buf: &mut [u8]) -> Result<Option<Vec<u8>>> {
+        let len = self
+            .socket
+            .recv(buf)
+            .await
+            .context("failed to receive from media socket")?;
+        let datagram = &buf[..len];
+        let Some((&kind, rest)) = datagram.split_first() else {
+            bail!("empty datagram on media socket");
+        };
+
+        match kind {
+            PACKET_KIND_MEDIA => {
+                anyhow::ensure!(rest.len() >= 8, "media datagram missing sequence number");
+                let (seq_bytes, ciphertext) = rest.split_at(8);
+                let seq = u64::from_be_bytes(seq_bytes.try_into().expect("split_at(8) yields 8 bytes"));
+                // Authenticate before touching the replay window: recording
+                // `seq` as seen for a datagram that turns out to be forged
+                // would let an attacker consume a legitimate future frame's
+                // sequence number with garbage ciphertext, causing the real
+                // frame to then be rejected as "replayed" (a one-packet DoS).
+                let plaintext = open_frame(&self.keys.recv_key, seq, ciphertext)?;
+                anyhow::ensure!(
+                    self.replay_window.check_and_record(seq),
+                    "rejecting replayed or duplicate media frame seq={seq}"
+                );
+                Ok(Some(plaintext))
+            }
+            PACKET_KIND_REKEY_OFFER => {
+                let peer_ephemeral_public = parse_rekey_packet(
+                    datagram,
+                    PACKET_KIND_REKEY_OFFER,
+                    &self.keys.recv_key,
+                    b"dc voice rekey offer",
+                )?;
+                let our_ephemeral = EphemeralSecret::random_from_rng(rand::rngs::OsRng);
+                let our_ephemeral_public = PublicKey::from(&our_ephemeral).to_bytes();
+                let new_keys = ratchet_session_keys(
+                    &self.keys,
+                    &our_ephemeral,
+                    peer_ephemeral_public,
+                    self.we_are_initiator,
+                )?;
+                let ack = build_rekey_packet(
+                    PACKET_KIND_REKEY_ACK,
+                    &self.keys.send_key,
+                    b"dc voice rekey ack",
+                    our_ephemeral_public,
+                )?;
+                self.socket
+                    .send(&ack)
+                    .await
+                    .context("failed to send rekey ack")?;
+                self.rotate_to(new_keys);
+                Ok(None)
+            }
+            PACKET_KIND_REKEY_ACK => {
+                let our_ephemeral = self
+                    .pending_rekey
+                    .take()
+                    .context("received a rekey ack without a pending rekey offer")?;
+                let peer_ephemeral_public = parse_rekey_packet(
+                    datagram,
+                    PACKET_KIND_REKEY_ACK,
+                    &self.keys.recv_key,
+                    b"dc voice rekey ack",
+                )?;
+                let new_keys = ratchet_session_keys(
+                    &self.keys,
+                    &our_ephemeral,
+                    peer_ephemeral_public,
+                    self.we_are_initiator,
+                )?;
+                self.rotate_to(new_keys);
+                Ok(None)
+            }
+            other => bail!("unknown media socket packet kind {other}"),
+        }
+    }
+
+    /// Installs `new_keys` and resets the per-epoch sequence-number state
+    /// that's no longer meaningful once the key it was tracking has changed.
+    fn rotate_to(&mut self, new_keys: SessionKeys) {
+        self.keys = new_keys;
+        self.send_seq = 0;
+        self.replay_window = ReplayWindow::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handshake_rejects_untrusted_peer() {
+        let identity = StaticIdentity::random();
+        let trusted = TrustedPeers::new();
+        let peer = StaticIdentity::random();
+        let our_ephemeral = x25519_dalek::EphemeralSecret::random_from_rng(rand::rngs::OsRng);
+        let peer_ephemeral = x25519_dalek::EphemeralSecret::random_from_rng(rand::rngs::OsRng);
+        let peer_ephemeral_public = x25519_dalek::PublicKey::from(&peer_ephemeral).to_bytes();
+
+        let result = complete_handshake(
+            &identity,
+            &trusted,
+            peer.public_key(),
+            our_ephemeral,
+            peer_ephemeral_public,
+            true,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_handshake_keys_are_mirrored() {
+        let alice = StaticIdentity::random();
+        let bob = StaticIdentity::random();
+        let mut alice_trusts = TrustedPeers::new();
+        alice_trusts.trust(bob.public_key());
+        let mut bob_trusts = TrustedPeers::new();
+        bob_trusts.trust(alice.public_key());
+
+        let alice_ephemeral = x25519_dalek::EphemeralSecret::random_from_rng(rand::rngs::OsRng);
+        let alice_ephemeral_public = x25519_dalek::PublicKey::from(&alice_ephemeral).to_bytes();
+        let bob_ephemeral = x25519_dalek::EphemeralSecret::random_from_rng(rand::rngs::OsRng);
+        let bob_ephemeral_public = x25519_dalek::PublicKey::from(&bob_ephemeral).to_bytes();
+
+        let alice_keys = complete_handshake(
+            &alice,
+            &alice_trusts,
+            bob.public_key(),
+            alice_ephemeral,
+            bob_ephemeral_public,
+            true,
+        )
+        .unwrap();
+        let bob_keys = complete_handshake(
+            &bob,
+            &bob_trusts,
+            alice.public_key(),
+            bob_ephemeral,
+            alice_ephemeral_public,
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(alice_keys.send_key, bob_keys.recv_key);
+        assert_eq!(alice_keys.recv_key, bob_keys.send_key);
+    }
+
+    #[test]
+    fn test_replay_window_rejects_duplicates() {
+        let mut window = ReplayWindow::new();
+        assert!(window.check_and_record(5));
+        assert!(!window.check_and_record(5));
+        assert!(window.check_and_record(3));
+        assert!(!window.check_and_record(3));
+        assert!(window.check_and_record(10));
+    }
+
+    #[test]
+    fn test_replay_window_accepts_out_of_order() {
+        let mut window = ReplayWindow::new();
+        assert!(window.check_and_record(10));
+        assert!(window.check_and_record(8));
+        assert!(window.check_and_record(9));
+        assert!(!window.check_and_record(8));
+    }
+
+    #[test]
+    fn test_seal_open_frame_roundtrips() {
+        let send_key = [7u8; 32];
+        let sealed = seal_frame(&send_key, 42, b"opus frame payload").unwrap();
+        let opened = open_frame(&send_key, 42, &sealed).unwrap();
+        assert_eq!(opened, b"opus frame payload");
+    }
+
+    #[test]
+    fn test_open_frame_rejects_tampered_ciphertext() {
+        let send_key = [7u8; 32];
+        let mut sealed = seal_frame(&send_key, 1, b"hello").unwrap();
+        sealed[0] ^= 0x01;
+        assert!(open_frame(&send_key, 1, &sealed).is_err());
+    }
+
+    #[test]
+    fn test_open_frame_rejects_wrong_sequence_number() {
+        let send_key = [7u8; 32];
+        let sealed = seal_frame(&send_key, 1, b"hello").unwrap();
+        assert!(open_frame(&send_key, 2, &sealed).is_err());
+    }
+
+    #[test]
+    fn test_rekey_packet_roundtrips_and_rejects_tamper() {
+        let session_key = [9u8; 32];
+        let ephemeral_public = [3u8; 32];
+        let packet = build_rekey_packet(
+            PACKET_KIND_REKEY_OFFER,
+            &session_key,
+            b"dc voice rekey offer",
+            ephemeral_public,
+        )
+        .unwrap();
+
+        let parsed = parse_rekey_packet(
+            &packet,
+            PACKET_KIND_REKEY_OFFER,
+            &session_key,
+            b"dc voice rekey offer",
+        )
+        .unwrap();
+        assert_eq!(parsed, ephemeral_public);
+
+        let mut tampered = packet.clone();
+        tampered[1] ^= 0x01;
+        assert!(parse_rekey_packet(
+            &tampered,
+            PACKET_KIND_REKEY_OFFER,
+            &session_key,
+            b"dc voice rekey offer"
+        )
+        .is_err());
+    }
+
+    fn session_pair() -> (SessionKeys, SessionKeys) {
+        let alice = StaticIdentity::random();
+        let bob = StaticIdentity::random();
+        let mut alice_trusts = TrustedPeers::new();
+        alice_trusts.trust(bob.public_key());
+        let mut bob_trusts = TrustedPeers::new();
+        bob_trusts.trust(alice.public_key());
+
+        let alice_ephemeral = EphemeralSecret::random_from_rng(rand::rngs::OsRng);
+        let alice_ephemeral_public = PublicKey::from(&alice_ephemeral).to_bytes();
+        let bob_ephemeral = EphemeralSecret::random_from_rng(rand::rngs::OsRng);
+        let bob_ephemeral_public = PublicKey::from(&bob_ephemeral).to_bytes();
+
+        let alice_keys = complete_handshake(
+            &alice,
+            &alice_trusts,
+            bob.public_key(),
+            alice_ephemeral,
+            bob_ephemeral_public,
+            true,
+        )
+        .unwrap();
+        let bob_keys = complete_handshake(
+            &bob,
+            &bob_trusts,
+            alice.public_key(),
+            bob_ephemeral,
+            alice_ephemeral_public,
+            false,
+        )
+        .unwrap();
+        (alice_keys, bob_keys)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_media_socket_roundtrips_frames_and_rejects_replay() {
+        let (alice_keys, bob_keys) = session_pair();
+
+        let mut alice = MediaSocket::connect(
+            "127.0.0.1:0".parse().unwrap(),
+            "127.0.0.1:0".parse().unwrap(),
+            alice_keys,
+            true,
+        )
+        .await
+        .unwrap();
+        let alice_addr = alice.socket.local_addr().unwrap();
+        let mut bob = MediaSocket::connect("127.0.0.1:0".parse().unwrap(), alice_addr, bob_keys, false)
+            .await
+            .unwrap();
+        let bob_addr = bob.socket.local_addr().unwrap();
+        alice.socket.connect(bob_addr).await.unwrap();
+
+        alice.send_frame(b"frame one").await.unwrap();
+        let mut buf = [0u8; 1500];
+        let received = bob.recv(&mut buf).await.unwrap();
+        assert_eq!(received, Some(b"frame one".to_vec()));
+
+        // Replaying the same datagram is rejected.
+        let mut replayed = vec![PACKET_KIND_MEDIA];
+        replayed.extend_from_slice(&0u64.to_be_bytes());
+        replayed.extend_from_slice(&seal_frame(&alice.keys.send_key, 0, b"frame one").unwrap());
+        alice.socket.send(&replayed).await.unwrap();
+        assert!(bob.recv(&mut buf).await.is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_media_socket_spoofed_frame_does_not_burn_sequence_number() {
+        let (alice_keys, bob_keys) = session_pair();
+
+        let mut alice = MediaSocket::connect(
+            "127.0.0.1:0".parse().unwrap(),
+            "127.0.0.1:0".parse().unwrap(),
+            alice_keys,
+            true,
+        )
+        .await
+        .unwrap();
+        let alice_addr = alice.socket.local_addr().unwrap();
+        let mut bob = MediaSocket::connect("127.0.0.1:0".parse().unwrap(), alice_addr, bob_keys, false)
+            .await
+            .unwrap();
+        let bob_addr = bob.socket.local_addr().unwrap();
+        alice.socket.connect(bob_addr).await.unwrap();
+
+        // An attacker spoofs a datagram at seq 0 with garbage ciphertext,
+        // before Alice's real frame at seq 0 arrives.
+        let mut forged = Vec::new();
+        forged.push(PACKET_KIND_MEDIA);
+        forged.extend_from_slice(&0u64.to_be_bytes());
+        forged.extend_from_slice(&[0xAAu8; 32]);
+        alice.socket.send(&forged).await.unwrap();
+
+        let mut buf = [0u8; 1500];
+        assert!(bob.recv(&mut buf).await.is_err());
+
+        // Alice's real frame at the same sequence number must still be
+        // accepted: the forged datagram must not have been recorded as seen.
+        alice.send_frame(b"real frame").await.unwrap();
+        let received = bob.recv(&mut buf).await.unwrap();
+        assert_eq!(received, Some(b"real frame".to_vec()));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_media_socket_rekey_rotates_keys_on_both_ends() {
+        let (alice_keys, bob_keys) = session_pair();
+
+        let mut alice = MediaSocket::connect(
+            "127.0.0.1:0".parse().unwrap(),
+            "127.0.0.1:0".parse().unwrap(),
+            alice_keys,
+            true,
+        )
+        .await
+        .unwrap();
+        let alice_addr = alice.socket.local_addr().unwrap();
+        let mut bob = MediaSocket::connect("127.0.0.1:0".parse().unwrap(), alice_addr, bob_keys, false)
+            .await
+            .unwrap();
+        let bob_addr = bob.socket.local_addr().unwrap();
+        alice.socket.connect(bob_addr).await.unwrap();
+
+        alice.initiate_rekey().await.unwrap();
+        let mut buf = [0u8; 1500];
+        // Bob receives the offer, rotates, and sends back an ack.
+        assert_eq!(bob.recv(&mut buf).await.unwrap(), None);
+        // Alice receives the ack and completes the ratchet.
+        assert_eq!(alice.recv(&mut buf).await.unwrap(), None);
+
+        assert_eq!(alice.keys.send_key, bob.keys.recv_key);
+        assert_eq!(alice.keys.recv_key, bob.keys.send_key);
+
+        alice.send_frame(b"post-rekey frame").await.unwrap();
+        let received = bob.recv(&mut buf).await.unwrap();
+        assert_eq!(received, Some(b"post-rekey frame".to_vec()));
+    }
+}
@@ -10,7 +10,9 @@ use num_traits::FromPrimitive;
 use crate::aheader::{Aheader, EncryptPreference};
 use crate::config::Config;
 use crate::context::Context;
-use crate::key::{load_self_public_key, load_self_secret_key, SignedPublicKey};
+use crate::key::{
+    load_self_public_key, load_self_public_keyring, load_self_secret_key, SignedPublicKey,
+};
 use crate::peerstate::Peerstate;
 use crate::pgp;
 
@@ -69,28 +71,41 @@ impl EncryptHelper {
     ///
     /// Returns an error if there are recipients
     /// other than self, but no recipient keys are available.
-    pub(crate) fn encryption_keyring(
+    pub(crate) async fn encryption_keyring(
         &self,
         context: &Context,
         verified: bool,
         peerstates: &[(Option<Peerstate>, String)],
     ) -> Result<(Vec<SignedPublicKey>, BTreeSet<String>)> {
-        // Encrypt to self unconditionally,
-        // even for a single-device setup.
-        let mut keyring = vec![self.public_key.clone()];
+        // Encrypt to self unless the user opted out via `Config::EncryptForSelf`,
+        // e.g. for ephemeral/single-shot scenarios. Include every locally
+        // registered device's public key (not just this one), so a message
+        // sent from the phone remains readable on the laptop without a
+        // shared private key.
+        let mut keyring = if context.get_config_bool(Config::EncryptForSelf).await? {
+            load_self_public_keyring(context).await?
+        } else {
+            Vec::new()
+        };
         let mut missing_key_addresses = BTreeSet::new();
 
         if peerstates.is_empty() {
+            anyhow::ensure!(
+                !keyring.is_empty(),
+                "No recipient keys are available, cannot encrypt"
+            );
             return Ok((keyring, missing_key_addresses));
         }
 
         let mut verifier_addresses: Vec<&str> = Vec::new();
+        let mut recipient_key_count = 0;
 
         for (peerstate, addr) in peerstates {
             if let Some(peerstate) = peerstate {
                 if let Some(key) = peerstate.clone().take_key(verified) {
                     keyring.push(key);
                     verifier_addresses.push(addr);
+                    recipient_key_count += 1;
                 } else {
                     warn!(context, "Encryption key for {addr} is missing.");
                     missing_key_addresses.insert(addr.clone());
@@ -101,11 +116,7 @@ impl EncryptHelper {
             }
         }
 
-        debug_assert!(
-            !keyring.is_empty(),
-            "At least our own key is in the keyring"
-        );
-        if keyring.len() <= 1 {
+        if recipient_key_count == 0 {
             bail!("No recipient keys are available, cannot encrypt");
         }
 
@@ -130,6 +141,12 @@ impl EncryptHelper {
     }
 
     /// Tries to encrypt the passed in `mail`.
+    ///
+    /// Note: [`crate::pgp::CryptoPreference`] negotiation (picking the
+    /// strongest symmetric algorithm common to every recipient) needs each
+    /// recipient's preference carried on `Peerstate` and advertised via
+    /// `Aheader`, neither of which is wired up yet in this tree. Until that
+    /// lands, this always uses [`pgp::SYMMETRIC_KEY_ALGORITHM`].
     pub async fn encrypt(
         self,
         context: &Context,
@@ -143,7 +160,14 @@ impl EncryptHelper {
         let cursor = Cursor::new(&mut raw_message);
         mail_to_encrypt.clone().write_part(cursor).ok();
 
-        let ctext = pgp::pk_encrypt(&raw_message, keyring, Some(sign_key), compress).await?;
+        let ctext = pgp::pk_encrypt(
+            raw_message,
+            keyring,
+            Some(sign_key),
+            compress,
+            pgp::SYMMETRIC_KEY_ALGORITHM,
+        )
+        .await?;
 
         Ok(ctext)
     }
@@ -155,11 +179,49 @@ impl EncryptHelper {
         let mut buffer = Vec::new();
         let cursor = Cursor::new(&mut buffer);
         mail.clone().write_part(cursor).ok();
-        let signature = pgp::pk_calc_signature(&buffer, &sign_key)?;
+        let signer = pgp::InMemorySigner::new(sign_key)?;
+        let signature = pgp::pk_calc_signature(buffer, &signer)?;
         Ok(signature)
     }
 }
 
+/// Encrypts `mail_to_encrypt` for a verified group secured by MLS/TreeKEM
+/// (see [`crate::mls`]) instead of [`EncryptHelper::encrypt`]'s flat PGP
+/// keyring. `sender_leaf_index`/`ratchet_index` identify our own leaf and
+/// our position in this epoch's sending ratchet, the same pair the
+/// recipients use with [`crate::mls::GroupEncryptHelper::open_application_message`]
+/// to recover the plaintext.
+pub fn encrypt_for_verified_group(
+    group: &crate::mls::GroupEncryptHelper,
+    sender_leaf_index: u32,
+    ratchet_index: u64,
+    mail_to_encrypt: &MimePart<'static>,
+) -> Result<Vec<u8>> {
+    let mut raw_message = Vec::new();
+    let cursor = Cursor::new(&mut raw_message);
+    mail_to_encrypt.clone().write_part(cursor).ok();
+    group.seal_application_message(sender_leaf_index, ratchet_index, &raw_message)
+}
+
+/// Seals `mail_to_encrypt` under the next one-time key of `ratchet` (see
+/// [`crate::ratchet::RatchetState`]), layered under this module's PGP
+/// envelope for the per-message forward secrecy a flat PGP keyring alone
+/// doesn't provide. Returns the chain index [`crate::ratchet::RatchetState::open_message`]
+/// needs alongside the sealed bytes to derive the matching key on the
+/// recipient's side.
+///
+/// Caveat: this function does not persist `ratchet`; the caller loads and
+/// keeps its own `RatchetState` across calls.
+pub fn encrypt_with_ratchet(
+    ratchet: &mut crate::ratchet::RatchetState,
+    mail_to_encrypt: &MimePart<'static>,
+) -> Result<(u64, Vec<u8>)> {
+    let mut raw_message = Vec::new();
+    let cursor = Cursor::new(&mut raw_message);
+    mail_to_encrypt.clone().write_part(cursor).ok();
+    ratchet.seal_message(&raw_message)
+}
+
 /// Ensures a private key exists for the configured user.
 ///
 /// Normally the private key is generated when the first message is
@@ -183,6 +245,40 @@ mod tests {
     use crate::receive_imf::receive_imf;
     use crate::test_utils::{bob_keypair, TestContext, TestContextManager};
 
+    #[test]
+    fn test_encrypt_for_verified_group_roundtrips() {
+        use crate::key::DcKey;
+        use crate::mls::GroupEncryptHelper;
+        use crate::test_utils::alice_keypair;
+
+        let founder_fingerprint = alice_keypair().public.dc_fingerprint();
+        let group = GroupEncryptHelper::new(founder_fingerprint, vec![1, 2, 3]).unwrap();
+
+        let mail = MimePart::new("text/plain", "hi group");
+        let sealed = encrypt_for_verified_group(&group, 0, 0, &mail).unwrap();
+
+        let opened = group.open_application_message(0, 0, &sealed).unwrap();
+        assert!(String::from_utf8_lossy(&opened).contains("hi group"));
+    }
+
+    #[test]
+    fn test_encrypt_with_ratchet_roundtrips() {
+        use crate::ratchet::RatchetState;
+
+        let shared_secret = [7u8; 32];
+        let mut alice = RatchetState::new(shared_secret);
+        let mut bob = RatchetState::new(shared_secret);
+        alice.establish_as_initiator(bob.our_dh_public()).unwrap();
+
+        let mail = MimePart::new("text/plain", "hi bob");
+        let (chain_index, sealed) = encrypt_with_ratchet(&mut alice, &mail).unwrap();
+
+        let opened = bob
+            .open_message(alice.our_dh_public(), chain_index, &sealed)
+            .unwrap();
+        assert!(String::from_utf8_lossy(&opened).contains("hi bob"));
+    }
+
     mod ensure_secret_key_exists {
         use super::*;
 
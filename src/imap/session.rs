@@ -1,14 +1,23 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 use std::ops::{Deref, DerefMut};
+use std::time::Duration;
 
-use anyhow::{Context as _, Result};
-use async_imap::types::Mailbox;
+use anyhow::{bail, Context as _, Result};
+use async_imap::types::{Flag, Mailbox};
 use async_imap::Session as ImapSession;
 use futures::TryStreamExt;
 
 use crate::imap::capabilities::Capabilities;
 use crate::net::session::SessionStream;
 
+/// RFC 2177 recommends re-issuing `IDLE` before 29 minutes pass, so that the server (or a
+/// NAT/proxy in between) doesn't drop the connection for being idle too long.
+const MAX_IDLE_DURATION: Duration = Duration::from_secs(29 * 60);
+
+/// How often [`IdleHandle::next_event`] polls with `NOOP` when the server doesn't support
+/// `IDLE` ([`Session::can_idle`] is `false`).
+const NOOP_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
 /// Prefetch:
 /// - Message-ID to check if we already have the message.
 /// - In-Reply-To and References to check if message is a reply to chat message.
@@ -44,6 +53,40 @@ pub(crate) struct Session {
     ///
     /// Should be false if no folder is currently selected.
     pub new_mail: bool,
+
+    /// The highest MODSEQ observed for `selected_folder` via [`Self::prefetch_changed`]. Feed
+    /// this back in as `last_modseq` on the next call for that folder.
+    pub highest_modseq: Option<u64>,
+
+    /// The full UID set last observed in `selected_folder` via [`Self::prefetch_changed`]. Used
+    /// to detect expunges on servers that support CONDSTORE but not QRESYNC, by diffing this
+    /// against a fresh full UID listing (QRESYNC servers report expunges directly instead, via
+    /// `VANISHED`, so don't need this).
+    known_uids: BTreeSet<u32>,
+}
+
+/// Everything that changed in a folder since some previously observed MODSEQ, as returned by
+/// [`Session::prefetch_changed`].
+#[derive(Debug, Default)]
+pub(crate) struct SyncResult {
+    /// `(UID, flags)` for every message whose flags (and therefore MODSEQ) changed.
+    pub changed_flags: Vec<(u32, Vec<Flag<'static>>)>,
+
+    /// UIDs of messages that were expunged from the folder in the meantime.
+    pub vanished: Vec<u32>,
+
+    /// The highest MODSEQ now known for this folder. Pass this back in as `last_modseq` on the
+    /// next call.
+    pub new_highest_modseq: u64,
+}
+
+/// A single resource limit reported for a quota root (RFC 2087's `QUOTA` response), e.g.
+/// `STORAGE` (in 1024-byte units) or `MESSAGE` (message count).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct QuotaResource {
+    pub name: String,
+    pub usage: u64,
+    pub limit: u64,
 }
 
 impl Deref for Session {
@@ -72,6 +115,8 @@ impl Session {
             selected_mailbox: None,
             selected_folder_needs_expunge: false,
             new_mail: false,
+            highest_modseq: None,
+            known_uids: BTreeSet::new(),
         }
     }
 
@@ -110,6 +155,78 @@ impl Session {
         Ok(list)
     }
 
+    /// Returns whether mailbox `name` exists on the server, via a `LIST` restricted to that
+    /// exact name (cheaper than listing everything, see [`Self::list_folders`]).
+    pub(crate) async fn folder_exists(&mut self, name: &str) -> Result<bool> {
+        let list: Vec<async_imap::types::Name> = self
+            .list(Some(""), Some(name))
+            .await
+            .context("LIST failed")?
+            .try_collect()
+            .await?;
+        Ok(!list.is_empty())
+    }
+
+    /// Creates `name` as a new mailbox (RFC 3501 `CREATE`). Needed so the crate can provision
+    /// its own chat folders rather than assuming they already exist on the server.
+    ///
+    /// Not yet called anywhere, nor are [`Self::delete_folder`]/[`Self::rename_folder`]/
+    /// [`Self::subscribe`]/[`Self::unsubscribe`]: the folder-provisioning code that would
+    /// reconcile the account's configured chat folders against the server lives outside this
+    /// module (this snapshot has no `imap` parent module or account-configuration code to wire
+    /// these into yet).
+    pub(crate) async fn create_folder(&mut self, name: &str) -> Result<()> {
+        self.inner.create(name).await.context("CREATE failed")?;
+        Ok(())
+    }
+
+    /// Deletes mailbox `name` (RFC 3501 `DELETE`). If `name` is `selected_folder`, also clears
+    /// the session's selected-folder state, since it would otherwise reference a mailbox that
+    /// no longer exists.
+    pub(crate) async fn delete_folder(&mut self, name: &str) -> Result<()> {
+        self.inner.delete(name).await.context("DELETE failed")?;
+        if self.selected_folder.as_deref() == Some(name) {
+            self.clear_selected_folder();
+        }
+        Ok(())
+    }
+
+    /// Renames mailbox `old` to `new` (RFC 3501 `RENAME`). If `old` is `selected_folder`, also
+    /// clears the session's selected-folder state, since it would otherwise reference a mailbox
+    /// that no longer exists under that name.
+    pub(crate) async fn rename_folder(&mut self, old: &str, new: &str) -> Result<()> {
+        self.inner.rename(old, new).await.context("RENAME failed")?;
+        if self.selected_folder.as_deref() == Some(old) {
+            self.clear_selected_folder();
+        }
+        Ok(())
+    }
+
+    /// Subscribes to mailbox `name` (RFC 3501 `SUBSCRIBE`).
+    pub(crate) async fn subscribe(&mut self, name: &str) -> Result<()> {
+        self.inner.subscribe(name).await.context("SUBSCRIBE failed")?;
+        Ok(())
+    }
+
+    /// Unsubscribes from mailbox `name` (RFC 3501 `UNSUBSCRIBE`).
+    pub(crate) async fn unsubscribe(&mut self, name: &str) -> Result<()> {
+        self.inner
+            .unsubscribe(name)
+            .await
+            .context("UNSUBSCRIBE failed")?;
+        Ok(())
+    }
+
+    /// Clears all session state referencing `selected_folder`, used after a
+    /// `delete_folder`/`rename_folder` call that affects it, since the folder it names no
+    /// longer exists (or no longer exists under that name).
+    fn clear_selected_folder(&mut self) {
+        self.selected_folder = None;
+        self.selected_mailbox = None;
+        self.new_mail = false;
+        self.selected_folder_needs_expunge = false;
+    }
+
     /// Prefetch all messages greater than or equal to `uid_next`. Returns a list of fetch results
     /// in the order of ascending delivery time to the server (INTERNALDATE).
     pub(crate) async fn prefetch(
@@ -141,4 +258,717 @@ impl Session {
 
         Ok(msgs.into_iter().map(|((_, uid), msg)| (uid, msg)).collect())
     }
+
+    /// Like [`Self::prefetch`], but yields each fetch result as a [`futures::Stream`] item as
+    /// soon as it arrives off the connection, instead of buffering all of them into memory
+    /// first. Messages come back in whatever order the server happens to send them in, *not*
+    /// sorted by INTERNALDATE the way [`Self::prefetch`]'s `Vec` is — use
+    /// [`Self::prefetch_chunked`] if that ordering matters but buffering the whole mailbox
+    /// isn't affordable.
+    ///
+    /// Not yet called anywhere, nor is [`Self::prefetch_chunked`]: the message-receive pipeline
+    /// that would consume either stream instead of [`Self::prefetch`]'s buffered `Vec` lives
+    /// outside this module (this snapshot has no `imap` parent module or receive pipeline to
+    /// wire it into yet).
+    pub(crate) async fn prefetch_stream(
+        &mut self,
+        uid_next: u32,
+    ) -> Result<impl futures::Stream<Item = Result<(u32, async_imap::types::Fetch)>> + '_> {
+        let set = format!("{uid_next}:*");
+        let list = self
+            .uid_fetch(set, PREFETCH_FLAGS)
+            .await
+            .context("IMAP could not fetch")?;
+        Ok(list.map_err(anyhow::Error::from).try_filter_map(move |msg| {
+            futures::future::ready(Ok(msg
+                .uid
+                .filter(|&uid| uid >= uid_next)
+                .map(|uid| (uid, msg))))
+        }))
+    }
+
+    /// Like [`Self::prefetch_stream`], but fetches in UID windows of `chunk_size` messages and
+    /// sorts each window by INTERNALDATE before yielding it — the same ordering
+    /// [`Self::prefetch`] guarantees for its whole result — trading away some of the streaming
+    /// variant's memory-boundedness for an ordering guarantee that only costs `chunk_size`
+    /// buffered messages at a time, instead of the whole mailbox.
+    pub(crate) async fn prefetch_chunked(
+        &mut self,
+        uid_next: u32,
+        chunk_size: usize,
+    ) -> Result<impl futures::Stream<Item = Result<(u32, async_imap::types::Fetch)>> + '_> {
+        let mut uids = BTreeSet::new();
+        let mut list = self
+            .uid_fetch(format!("{uid_next}:*"), "(UID)")
+            .await
+            .context("IMAP could not fetch UIDs")?;
+        while let Some(msg) = list.try_next().await? {
+            if let Some(uid) = msg.uid.filter(|&uid| uid >= uid_next) {
+                uids.insert(uid);
+            }
+        }
+
+        let chunks: VecDeque<Vec<u32>> = uids
+            .into_iter()
+            .collect::<Vec<_>>()
+            .chunks(chunk_size.max(1))
+            .map(<[u32]>::to_vec)
+            .collect();
+
+        Ok(futures::stream::unfold(
+            (self, chunks, VecDeque::new()),
+            |(session, mut chunks, mut buffered)| async move {
+                loop {
+                    if let Some(item) = buffered.pop_front() {
+                        return Some((Ok(item), (session, chunks, buffered)));
+                    }
+                    let chunk = chunks.pop_front()?;
+                    match session.fetch_sorted_chunk(&chunk).await {
+                        Ok(sorted) => buffered = sorted,
+                        Err(e) => return Some((Err(e), (session, chunks, buffered))),
+                    }
+                }
+            },
+        ))
+    }
+
+    /// Fetches exactly the UIDs in `chunk`, sorted by INTERNALDATE — the same ordering
+    /// [`Self::prefetch`] produces for its whole result, but for a single window of UIDs.
+    async fn fetch_sorted_chunk(
+        &mut self,
+        chunk: &[u32],
+    ) -> Result<VecDeque<(u32, async_imap::types::Fetch)>> {
+        let set = chunk
+            .iter()
+            .map(u32::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+        let mut list = self
+            .uid_fetch(set, PREFETCH_FLAGS)
+            .await
+            .context("IMAP could not fetch")?;
+
+        let mut msgs = BTreeMap::new();
+        while let Some(msg) = list.try_next().await? {
+            if let Some(uid) = msg.uid {
+                msgs.insert((msg.internal_date(), uid), msg);
+            }
+        }
+        Ok(msgs.into_iter().map(|((_, uid), msg)| (uid, msg)).collect())
+    }
+
+    /// Incrementally syncs flag changes and expunges for `selected_folder` since `last_modseq`,
+    /// using RFC 7162 CONDSTORE/QRESYNC instead of [`Self::prefetch`]'s "only look at UIDs above
+    /// `uid_next`" approach, which is blind to changes on messages it already knows about.
+    ///
+    /// Requires CONDSTORE support ([`Self::can_condstore`]) and a selected folder; returns an
+    /// error otherwise.
+    ///
+    /// If the server also accepts a QRESYNC `SELECT`, it's used to ask the server directly for
+    /// `VANISHED (EARLIER)` UIDs (messages expunged while we weren't watching). If the server
+    /// doesn't understand that `SELECT` form, expunges are instead detected by diffing a full
+    /// current UID listing against the one seen on this `Session`'s previous call — so the
+    /// first `prefetch_changed()` after (re)connecting never reports any vanished messages,
+    /// since there's nothing yet to diff against.
+    ///
+    /// **The caller must compare `selected_mailbox`'s `uid_validity` against its previously
+    /// stored value before trusting this result**: if it changed, the server has reassigned
+    /// UIDs since we last looked, so everything cached locally (not just what's reported here)
+    /// must be discarded in favor of a full resync.
+    ///
+    /// Not yet called anywhere: the sync loop that would track `last_modseq` per folder and
+    /// decide between this and [`Self::prefetch`] lives outside this module (this snapshot has
+    /// no `imap` parent module or job-scheduling code to wire it into yet).
+    pub(crate) async fn prefetch_changed(&mut self, last_modseq: u64) -> Result<SyncResult> {
+        if !self.can_condstore() {
+            bail!("prefetch_changed() requires a CONDSTORE-capable IMAP server");
+        }
+        let folder = self
+            .selected_folder
+            .clone()
+            .context("prefetch_changed() called without a selected folder")?;
+
+        let vanished_via_qresync = match self.qresync_reselect(&folder, last_modseq).await {
+            Ok(vanished) => Some(vanished),
+            // The server doesn't support this `SELECT` form (or some other transient failure);
+            // fall back to full-UID-diff expunge detection below.
+            Err(_) => None,
+        };
+
+        let query = format!("(UID FLAGS) (CHANGEDSINCE {last_modseq})");
+        let mut list = self
+            .uid_fetch("1:*", query)
+            .await
+            .context("IMAP could not fetch changed messages")?;
+
+        let mut changed_flags = Vec::new();
+        let mut new_highest_modseq = last_modseq;
+        while let Some(msg) = list.try_next().await? {
+            let Some(uid) = msg.uid else { continue };
+            if let Some(modseq) = msg.modseq {
+                new_highest_modseq = new_highest_modseq.max(modseq);
+            }
+            changed_flags.push((uid, msg.flags().map(Flag::to_owned).collect()));
+        }
+
+        let vanished = match vanished_via_qresync {
+            Some(vanished) => vanished,
+            None => self.vanished_via_full_uid_diff().await?,
+        };
+
+        self.highest_modseq = Some(new_highest_modseq);
+
+        Ok(SyncResult {
+            changed_flags,
+            vanished,
+            new_highest_modseq,
+        })
+    }
+
+    /// Re-selects `folder` with a QRESYNC parameter (RFC 7162 §3.2.5), updating
+    /// `selected_mailbox` from a follow-up plain `SELECT` of the folder, and returns the UIDs reported as
+    /// `VANISHED (EARLIER)` (messages expunged since `last_modseq`). Fails if the server
+    /// doesn't support QRESYNC, if we don't yet know the folder's UIDVALIDITY from a previous
+    /// `SELECT` (the `QRESYNC` parameter is meaningless without it), or on any other `SELECT`
+    /// failure.
+    async fn qresync_reselect(&mut self, folder: &str, last_modseq: u64) -> Result<Vec<u32>> {
+        // RFC 7162 §3.2.5: `QRESYNC (uidvalidity modseq [...])` — the UIDVALIDITY we last saw for
+        // this folder must come first, so the server can tell us to discard everything if it
+        // doesn't match its own. This is the value `selected_mailbox` holds from the `SELECT`
+        // that preceded this call, not anything this method itself has observed yet.
+        let uid_validity = self
+            .selected_mailbox
+            .as_ref()
+            .and_then(|mailbox| mailbox.uid_validity)
+            .context("no previously observed UIDVALIDITY to QRESYNC against")?;
+        let command = format!("SELECT \"{folder}\" (QRESYNC ({uid_validity} {last_modseq}))");
+        let response = self
+            .run_command_and_read_response(&command)
+            .await
+            .context("QRESYNC SELECT failed")?;
+        let response = String::from_utf8_lossy(&response);
+        self.selected_mailbox = self.select(&folder).await.ok();
+        Ok(parse_vanished(&response))
+    }
+
+    /// Detects expunges the hard way, for a CONDSTORE-only server that doesn't support QRESYNC:
+    /// a UID we used to know about that's missing from a fresh full UID listing must have been
+    /// expunged.
+    async fn vanished_via_full_uid_diff(&mut self) -> Result<Vec<u32>> {
+        let mut full_uids = BTreeSet::new();
+        let mut list = self
+            .uid_fetch("1:*", "(UID)")
+            .await
+            .context("IMAP could not fetch full UID set")?;
+        while let Some(msg) = list.try_next().await? {
+            if let Some(uid) = msg.uid {
+                full_uids.insert(uid);
+            }
+        }
+
+        let vanished = self.known_uids.difference(&full_uids).copied().collect();
+        self.known_uids = full_uids;
+        Ok(vanished)
+    }
+
+    /// Reads the IMAP METADATA `entries` for `mailbox` (RFC 5464's `GETMETADATA`). Entries with
+    /// no value set on the server are simply absent from the result, the same way a real
+    /// `GETMETADATA` response omits them rather than reporting `NIL`.
+    ///
+    /// This is a natural fit for storing small bits of per-account state directly on the server
+    /// instead of only locally — e.g. a device push token under `/private/devicetoken`, which
+    /// pairs with [`Self::can_push`], or a sync cursor.
+    ///
+    /// Errors if the server doesn't support METADATA ([`Self::can_metadata`]).
+    ///
+    /// Not yet called anywhere, nor is [`Self::set_metadata`]: the device-token/sync-cursor
+    /// storage code that would use these lives outside this module (this snapshot has no
+    /// `imap` parent module or push/config code to wire them into yet).
+    pub(crate) async fn get_metadata(
+        &mut self,
+        mailbox: &str,
+        entries: &[&str],
+    ) -> Result<BTreeMap<String, Option<String>>> {
+        if !self.can_metadata() {
+            bail!("get_metadata() requires a METADATA-capable IMAP server");
+        }
+        let entry_list = entries.join(" ");
+        let command = format!("GETMETADATA \"{mailbox}\" ({entry_list})");
+        let response = self
+            .run_command_and_read_response(&command)
+            .await
+            .context("GETMETADATA failed")?;
+        Ok(parse_metadata_response(&String::from_utf8_lossy(&response)))
+    }
+
+    /// Writes IMAP METADATA `entries` for `mailbox` (RFC 5464's `SETMETADATA`). A `None` value
+    /// deletes the entry.
+    ///
+    /// Errors if the server doesn't support METADATA ([`Self::can_metadata`]).
+    pub(crate) async fn set_metadata(
+        &mut self,
+        mailbox: &str,
+        entries: &[(&str, Option<&str>)],
+    ) -> Result<()> {
+        if !self.can_metadata() {
+            bail!("set_metadata() requires a METADATA-capable IMAP server");
+        }
+        let entry_list = entries
+            .iter()
+            .map(|(name, value)| match value {
+                Some(value) => format!("{name} \"{value}\""),
+                None => format!("{name} NIL"),
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        let command = format!("SETMETADATA \"{mailbox}\" ({entry_list})");
+        self.run_command_and_read_response(&command)
+            .await
+            .context("SETMETADATA failed")?;
+        Ok(())
+    }
+
+    /// Returns the quota root names that apply to `mailbox` (RFC 2087's `GETQUOTAROOT`).
+    /// Errors if the server doesn't support quotas ([`Self::can_check_quota`]).
+    ///
+    /// Not yet called anywhere, nor are [`Self::get_quota`]/[`Self::mailbox_usage_ratio`]: the
+    /// code that would warn a user before an over-quota `APPEND` lives outside this module
+    /// (this snapshot has no `imap` parent module or UI-facing account state to wire these
+    /// into yet).
+    pub(crate) async fn get_quota_roots(&mut self, mailbox: &str) -> Result<Vec<String>> {
+        if !self.can_check_quota() {
+            bail!("get_quota_roots() requires a QUOTA-capable IMAP server");
+        }
+        let command = format!("GETQUOTAROOT \"{mailbox}\"");
+        let response = self
+            .run_command_and_read_response(&command)
+            .await
+            .context("GETQUOTAROOT failed")?;
+        Ok(parse_quota_roots(&String::from_utf8_lossy(&response)))
+    }
+
+    /// Returns the resource limits for quota `root` (RFC 2087's `GETQUOTA`). Errors if the
+    /// server doesn't support quotas ([`Self::can_check_quota`]).
+    pub(crate) async fn get_quota(&mut self, root: &str) -> Result<Vec<QuotaResource>> {
+        if !self.can_check_quota() {
+            bail!("get_quota() requires a QUOTA-capable IMAP server");
+        }
+        let command = format!("GETQUOTA \"{root}\"");
+        let response = self
+            .run_command_and_read_response(&command)
+            .await
+            .context("GETQUOTA failed")?;
+        Ok(parse_quota(&String::from_utf8_lossy(&response)))
+    }
+
+    /// Returns the highest usage/limit fraction across all quota roots applying to
+    /// `selected_folder`, so a client can warn the user before an over-quota `APPEND` fails.
+    /// Returns `0.0` if there's no selected folder, no quota roots, or no resource with a
+    /// nonzero limit.
+    pub(crate) async fn mailbox_usage_ratio(&mut self) -> Result<f64> {
+        let Some(folder) = self.selected_folder.clone() else {
+            return Ok(0.0);
+        };
+        let mut highest = 0.0;
+        for root in self.get_quota_roots(&folder).await? {
+            for resource in self.get_quota(&root).await? {
+                if resource.limit > 0 {
+                    let ratio = resource.usage as f64 / resource.limit as f64;
+                    if ratio > highest {
+                        highest = ratio;
+                    }
+                }
+            }
+        }
+        Ok(highest)
+    }
+
+    /// Starts watching `selected_folder` for unsolicited mailbox changes, consuming `self` for
+    /// the duration: the connection is dedicated to `IDLE` (or `NOOP` polling, on servers
+    /// without `IDLE`) until [`IdleHandle::done`] hands the `Session` back.
+    ///
+    /// Use [`IdleHandle::next_event`] to wait for the next change.
+    ///
+    /// Not yet called anywhere: the connection-management code that would hold a `Session` in
+    /// this state between active syncs lives outside this module (this snapshot has no `imap`
+    /// parent module or connection pool to wire it into yet).
+    pub(crate) async fn idle(mut self) -> Result<IdleHandle> {
+        let known_exists = self
+            .selected_mailbox
+            .as_ref()
+            .map(|mailbox| mailbox.exists)
+            .unwrap_or(0);
+        let uses_idle = self.can_idle();
+        if uses_idle {
+            self.run_command("IDLE")
+                .await
+                .context("failed to start IDLE")?;
+        }
+        Ok(IdleHandle {
+            session: self,
+            uses_idle,
+            known_exists,
+        })
+    }
+}
+
+/// An unsolicited mailbox-state change observed while [`IdleHandle`] is watching a folder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum IdleEvent {
+    /// `* n EXISTS`: the mailbox now has `n` messages.
+    Exists(u32),
+    /// `* n EXPUNGE`: the message at sequence number `n` was removed.
+    Expunge(u32),
+    /// `* n RECENT`: the mailbox has `n` recent messages.
+    Recent(u32),
+}
+
+/// Returned by [`Session::idle`]: a `Session` that is either watching `selected_folder` via
+/// `IDLE`, or falling back to polling it with `NOOP`, until [`Self::done`] is called.
+#[derive(Debug)]
+pub(crate) struct IdleHandle {
+    session: Session,
+    uses_idle: bool,
+    known_exists: u32,
+}
+
+impl IdleHandle {
+    /// Ends watching (sending `DONE`, if `IDLE` is in use) and returns the `Session` to the
+    /// selected state.
+    pub(crate) async fn done(mut self) -> Result<Session> {
+        if self.uses_idle {
+            self.session
+                .run_command("DONE")
+                .await
+                .context("failed to end IDLE")?;
+        }
+        Ok(self.session)
+    }
+
+    /// Waits for the next unsolicited mailbox event, transparently re-issuing `IDLE` every
+    /// [`MAX_IDLE_DURATION`] (or polling with `NOOP` every [`NOOP_POLL_INTERVAL`], if `IDLE`
+    /// isn't in use). Sets `new_mail` on the underlying `Session` when an `EXISTS` count higher
+    /// than previously seen arrives.
+    pub(crate) async fn next_event(&mut self) -> Result<IdleEvent> {
+        loop {
+            let response = if self.uses_idle {
+                match tokio::time::timeout(MAX_IDLE_DURATION, self.session.read_untagged_line())
+                    .await
+                {
+                    Ok(line) => line.context("IDLE connection failed")?,
+                    Err(_) => {
+                        // Re-issue IDLE before the server (or a NAT/proxy in between) drops the
+                        // connection for being idle too long.
+                        self.session
+                            .run_command("DONE")
+                            .await
+                            .context("failed to end IDLE for reissue")?;
+                        self.session
+                            .run_command("IDLE")
+                            .await
+                            .context("failed to restart IDLE")?;
+                        continue;
+                    }
+                }
+            } else {
+                tokio::time::sleep(NOOP_POLL_INTERVAL).await;
+                self.session
+                    .run_command_and_read_response("NOOP")
+                    .await
+                    .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+                    .context("NOOP failed")?
+            };
+
+            let Some(event) = parse_idle_event(&response) else {
+                continue;
+            };
+            if let IdleEvent::Exists(n) = event {
+                if n > self.known_exists {
+                    self.session.new_mail = true;
+                }
+                self.known_exists = n;
+            }
+            return Ok(event);
+        }
+    }
+}
+
+/// Parses the first `* n EXISTS` / `* n EXPUNGE` / `* n RECENT` line (RFC 3501 §7.3.1, §7.4.1)
+/// found in `response` into an [`IdleEvent`]. A single `NOOP` or `IDLE` push can in principle
+/// carry more than one such line; callers that poll in a loop will pick up anything missed here
+/// on the next round.
+fn parse_idle_event(response: &str) -> Option<IdleEvent> {
+    for line in response.lines() {
+        let line = line.trim_start_matches('*').trim();
+        let Some((num, rest)) = line.split_once(' ') else { continue };
+        let Ok(num) = num.parse() else { continue };
+        match rest.trim() {
+            "EXISTS" => return Some(IdleEvent::Exists(num)),
+            "EXPUNGE" => return Some(IdleEvent::Expunge(num)),
+            "RECENT" => return Some(IdleEvent::Recent(num)),
+            _ => continue,
+        }
+    }
+    None
+}
+
+/// Parses `* METADATA "mailbox" (entry value entry value ...)` lines (RFC 5464 §4.3) out of a
+/// raw `GETMETADATA` response. A `NIL` value means the entry has no value set; such entries are
+/// left out of the result, matching how a real `GETMETADATA` response simply doesn't mention
+/// them.
+fn parse_metadata_response(response: &str) -> BTreeMap<String, Option<String>> {
+    let mut result = BTreeMap::new();
+    for line in response.lines() {
+        let Some(rest) = line.strip_prefix("* METADATA ") else {
+            continue;
+        };
+        // Skip the mailbox name (quoted or bare atom) and take the parenthesized entry/value
+        // list after it.
+        let Some(paren_start) = rest.find('(') else {
+            continue;
+        };
+        let Some(list) = rest
+            .get(paren_start + 1..)
+            .and_then(|s| s.strip_suffix(')'))
+        else {
+            continue;
+        };
+
+        let mut tokens = tokenize_metadata_list(list).into_iter();
+        while let Some(entry) = tokens.next() {
+            let Some(value) = tokens.next() else { break };
+            if value.eq_ignore_ascii_case("NIL") {
+                continue;
+            }
+            result.insert(entry, Some(unquote(&value)));
+        }
+    }
+    result
+}
+
+/// Splits a METADATA entry/value list into whitespace-separated tokens, treating a `"..."`
+/// quoted string as a single token so a value containing spaces isn't split apart.
+fn tokenize_metadata_list(list: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut chars = list.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        if c == '"' {
+            chars.next();
+            let mut token = String::from("\"");
+            for c in chars.by_ref() {
+                token.push(c);
+                if c == '"' {
+                    break;
+                }
+            }
+            tokens.push(token);
+        } else {
+            let mut token = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                token.push(c);
+                chars.next();
+            }
+            tokens.push(token);
+        }
+    }
+    tokens
+}
+
+/// Strips the surrounding `"` from a quoted METADATA token, if present.
+fn unquote(token: &str) -> String {
+    token
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .unwrap_or(token)
+        .to_string()
+}
+
+/// Parses `* QUOTAROOT "mailbox" root1 root2 ...` (RFC 2087 §5.1) out of a `GETQUOTAROOT`
+/// response, returning the quota root names (unquoted), if any.
+fn parse_quota_roots(response: &str) -> Vec<String> {
+    for line in response.lines() {
+        let Some(rest) = line.strip_prefix("* QUOTAROOT ") else {
+            continue;
+        };
+        let mut tokens = tokenize_metadata_list(rest).into_iter();
+        // The first token is the mailbox name itself, not a quota root.
+        tokens.next();
+        return tokens.map(|token| unquote(&token)).collect();
+    }
+    Vec::new()
+}
+
+/// Parses `* QUOTA root (STORAGE usage limit MESSAGE usage limit ...)` (RFC 2087 §5.2) out of a
+/// `GETQUOTA` response.
+fn parse_quota(response: &str) -> Vec<QuotaResource> {
+    let mut resources = Vec::new();
+    for line in response.lines() {
+        let Some(rest) = line.strip_prefix("* QUOTA ") else {
+            continue;
+        };
+        let Some(paren_start) = rest.find('(') else {
+            continue;
+        };
+        let Some(list) = rest
+            .get(paren_start + 1..)
+            .and_then(|s| s.strip_suffix(')'))
+        else {
+            continue;
+        };
+
+        let mut tokens = tokenize_metadata_list(list).into_iter();
+        while let Some(name) = tokens.next() {
+            let (Some(usage), Some(limit)) = (tokens.next(), tokens.next()) else {
+                break;
+            };
+            if let (Ok(usage), Ok(limit)) = (usage.parse(), limit.parse()) {
+                resources.push(QuotaResource { name, usage, limit });
+            }
+        }
+    }
+    resources
+}
+
+/// Parses all `* VANISHED (EARLIER) <uid-set>` lines (RFC 7162 §3.2.10) out of a raw IMAP
+/// response, expanding each UID set (e.g. `1,3:5`) into individual UIDs.
+fn parse_vanished(response: &str) -> Vec<u32> {
+    fn parse_uid_set(uid_set: &str) -> Vec<u32> {
+        let mut uids = Vec::new();
+        for part in uid_set.split(',') {
+            match part.split_once(':') {
+                Some((start, end)) => {
+                    if let (Ok(start), Ok(end)) = (start.parse(), end.parse()) {
+                        uids.extend(start..=end);
+                    }
+                }
+                None => {
+                    if let Ok(uid) = part.parse() {
+                        uids.push(uid);
+                    }
+                }
+            }
+        }
+        uids
+    }
+
+    response
+        .lines()
+        .filter_map(|line| {
+            line.strip_prefix("* VANISHED (EARLIER) ")
+                .or_else(|| line.strip_prefix("* VANISHED "))
+        })
+        .flat_map(|uid_set| parse_uid_set(uid_set.trim()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_vanished_expands_ranges_and_singletons() {
+        let response = "* VANISHED (EARLIER) 1,3:5,9\r\n";
+        assert_eq!(parse_vanished(response), vec![1, 3, 4, 5, 9]);
+    }
+
+    #[test]
+    fn test_parse_vanished_without_earlier() {
+        let response = "* VANISHED 7:8\r\n";
+        assert_eq!(parse_vanished(response), vec![7, 8]);
+    }
+
+    #[test]
+    fn test_parse_vanished_no_match_is_empty() {
+        assert_eq!(parse_vanished("* 12 EXISTS\r\n"), Vec::<u32>::new());
+    }
+
+    #[test]
+    fn test_parse_quota_roots() {
+        let response = "* QUOTAROOT \"INBOX\" \"#user/foo\" shared\r\n";
+        assert_eq!(
+            parse_quota_roots(response),
+            vec!["#user/foo".to_string(), "shared".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_parse_quota_roots_none() {
+        assert_eq!(parse_quota_roots("* OK done\r\n"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_parse_quota() {
+        let response = "* QUOTA \"#user/foo\" (STORAGE 1024 2048 MESSAGE 10 100)\r\n";
+        assert_eq!(
+            parse_quota(response),
+            vec![
+                QuotaResource {
+                    name: "STORAGE".to_string(),
+                    usage: 1024,
+                    limit: 2048,
+                },
+                QuotaResource {
+                    name: "MESSAGE".to_string(),
+                    usage: 10,
+                    limit: 100,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_metadata_response() {
+        let response =
+            "* METADATA \"INBOX\" (/private/comment \"a comment\" /shared/todo NIL)\r\n";
+        let metadata = parse_metadata_response(response);
+        assert_eq!(
+            metadata.get("/private/comment"),
+            Some(&Some("a comment".to_string()))
+        );
+        assert!(!metadata.contains_key("/shared/todo"));
+    }
+
+    #[test]
+    fn test_tokenize_metadata_list_keeps_quoted_strings_together() {
+        let tokens = tokenize_metadata_list("/private/comment \"a comment with spaces\" /a NIL");
+        assert_eq!(
+            tokens,
+            vec![
+                "/private/comment".to_string(),
+                "\"a comment with spaces\"".to_string(),
+                "/a".to_string(),
+                "NIL".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_idle_event_exists() {
+        assert_eq!(parse_idle_event("* 5 EXISTS\r\n"), Some(IdleEvent::Exists(5)));
+    }
+
+    #[test]
+    fn test_parse_idle_event_expunge() {
+        assert_eq!(
+            parse_idle_event("* 3 EXPUNGE\r\n"),
+            Some(IdleEvent::Expunge(3))
+        );
+    }
+
+    #[test]
+    fn test_parse_idle_event_recent() {
+        assert_eq!(parse_idle_event("* 2 RECENT\r\n"), Some(IdleEvent::Recent(2)));
+    }
+
+    #[test]
+    fn test_parse_idle_event_none_for_unrelated_line() {
+        assert_eq!(parse_idle_event("+ idling\r\n"), None);
+    }
 }
@@ -28,7 +28,97 @@ pub(crate) const HEADER_AUTOCRYPT: &str = "autocrypt-prefer-encrypt";
 pub const HEADER_SETUPCODE: &str = "passphrase-begin";
 
 /// Preferred symmetric encryption algorithm.
-const SYMMETRIC_KEY_ALGORITHM: SymmetricKeyAlgorithm = SymmetricKeyAlgorithm::AES128;
+pub(crate) const SYMMETRIC_KEY_ALGORITHM: SymmetricKeyAlgorithm = SymmetricKeyAlgorithm::AES128;
+
+/// Symmetric algorithms considered during the startup cipher-suite benchmark,
+/// in an arbitrary initial order (the benchmark reorders them by measured throughput).
+const BENCHMARKED_ALGORITHMS: [SymmetricKeyAlgorithm; 3] = [
+    SymmetricKeyAlgorithm::AES128,
+    SymmetricKeyAlgorithm::AES256,
+    SymmetricKeyAlgorithm::ChaCha20,
+];
+
+/// How long to spend benchmarking each candidate algorithm.
+const BENCHMARK_DURATION: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// Size of the scratch buffer encrypted repeatedly during the benchmark.
+const BENCHMARK_BUF_LEN: usize = 64 * 1024;
+
+/// A locally measured ordering of symmetric algorithms, fastest first.
+///
+/// Computed by [`CryptoPreference::benchmark`], which runs the full benchmark
+/// on every call; nothing in this module caches the result across starts.
+/// Intended to be advertised to peers via `Aheader`
+/// and negotiated via [`CryptoPreference::negotiate`] so a group can agree on
+/// the strongest algorithm common to all advertised lists, but that requires
+/// `Aheader`/`Peerstate` support this tree doesn't have yet, so
+/// [`crate::e2ee::EncryptHelper::encrypt`] does not use it for now.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CryptoPreference {
+    /// Algorithms ordered from fastest to slowest on this device.
+    pub ordered: Vec<SymmetricKeyAlgorithm>,
+}
+
+impl CryptoPreference {
+    /// Benchmarks [`BENCHMARKED_ALGORITHMS`] by encrypting a scratch buffer
+    /// for [`BENCHMARK_DURATION`] each and sorting by measured throughput
+    /// (bytes/sec), fastest first.
+    pub fn benchmark() -> Self {
+        let scratch = vec![0u8; BENCHMARK_BUF_LEN];
+        let mut results: Vec<(SymmetricKeyAlgorithm, f64)> = BENCHMARKED_ALGORITHMS
+            .iter()
+            .map(|alg| (*alg, Self::throughput(*alg, &scratch)))
+            .collect();
+        // Fastest (highest bytes/sec) first.
+        results.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        Self {
+            ordered: results.into_iter().map(|(alg, _)| alg).collect(),
+        }
+    }
+
+    /// Measures bytes/sec of `alg` by repeatedly symmetrically encrypting `scratch`
+    /// with a throwaway session key, for about [`BENCHMARK_DURATION`].
+    fn throughput(alg: SymmetricKeyAlgorithm, scratch: &[u8]) -> f64 {
+        let mut rng = thread_rng();
+        let key = alg.new_session_key(&mut rng);
+
+        let start = std::time::Instant::now();
+        let mut bytes_processed: u64 = 0;
+        while start.elapsed() < BENCHMARK_DURATION {
+            let mut buf = scratch.to_vec();
+            // We only care about relative throughput here, not about producing
+            // a valid ciphertext, so errors just stop this candidate's loop
+            // and leave it with whatever it measured so far.
+            if pgp::crypto::sym::encrypt(alg, &key, &mut buf).is_err() {
+                break;
+            }
+            bytes_processed = bytes_processed.saturating_add(scratch.len() as u64);
+        }
+        let secs = start.elapsed().as_secs_f64().max(f64::EPSILON);
+        bytes_processed as f64 / secs
+    }
+
+    /// Picks the first algorithm in `our_preference`'s order (fastest on this
+    /// device) that is present in every recipient's advertised list, falling
+    /// back to AES-256 when no algorithm is common to all of them.
+    pub fn negotiate<'a>(
+        our_preference: &CryptoPreference,
+        recipient_preferences: impl IntoIterator<Item = &'a CryptoPreference>,
+    ) -> SymmetricKeyAlgorithm {
+        let mut common: Vec<SymmetricKeyAlgorithm> = our_preference.ordered.clone();
+        for pref in recipient_preferences {
+            common.retain(|alg| pref.ordered.contains(alg));
+            if common.is_empty() {
+                return SymmetricKeyAlgorithm::AES256;
+            }
+        }
+        common
+            .into_iter()
+            .next()
+            .unwrap_or(SymmetricKeyAlgorithm::AES256)
+    }
+}
 
 /// Preferred cryptographic hash.
 const HASH_ALGORITHM: HashAlgorithm = HashAlgorithm::Sha256;
@@ -89,17 +179,51 @@ impl KeyPair {
     }
 }
 
+/// Default key validity period if [`KeyGenParams::valid_for`] is not set:
+/// about 3 years, a multi-year window that is still short enough to make
+/// renewal ([`renew_keypair`]) a routine, expected event.
+const DEFAULT_KEY_VALIDITY: std::time::Duration = std::time::Duration::from_secs(3 * 365 * 24 * 3600);
+
+/// Options controlling the key material produced by [`create_keypair`].
+///
+/// Expiration is treated as a renewable property of the self-signature
+/// (see [`renew_keypair`]), not an immutable property of the key, matching
+/// how other OpenPGP tooling treats it.
+#[derive(Debug, Clone)]
+pub struct KeyGenParams {
+    /// How long the primary key's self-signature and the subkey binding
+    /// signature should remain valid. `None` means non-expiring.
+    pub valid_for: Option<std::time::Duration>,
+    /// Algorithm for the signing primary key.
+    pub key_type: PgpKeyType,
+    /// Algorithm for the encryption subkey.
+    pub subkey_type: PgpKeyType,
+}
+
+impl Default for KeyGenParams {
+    fn default() -> Self {
+        Self {
+            valid_for: Some(DEFAULT_KEY_VALIDITY),
+            key_type: PgpKeyType::Ed25519Legacy,
+            subkey_type: PgpKeyType::ECDH(ECCCurve::Curve25519),
+        }
+    }
+}
+
 /// Create a new key pair.
 ///
 /// Both secret and public key consist of signing primary key and encryption subkey
 /// as [described in the Autocrypt standard](https://autocrypt.org/level1.html#openpgp-based-key-data).
 pub(crate) fn create_keypair(addr: EmailAddress) -> Result<KeyPair> {
-    let signing_key_type = PgpKeyType::Ed25519Legacy;
-    let encryption_key_type = PgpKeyType::ECDH(ECCCurve::Curve25519);
+    create_keypair_with_params(addr, KeyGenParams::default())
+}
 
+/// Like [`create_keypair`], but with explicit control over key types and validity.
+pub(crate) fn create_keypair_with_params(addr: EmailAddress, params: KeyGenParams) -> Result<KeyPair> {
     let user_id = format!("<{addr}>");
-    let key_params = SecretKeyParamsBuilder::default()
-        .key_type(signing_key_type)
+    let mut builder = SecretKeyParamsBuilder::default();
+    builder
+        .key_type(params.key_type)
         .can_certify(true)
         .can_sign(true)
         .primary_user_id(user_id)
@@ -118,17 +242,25 @@ pub(crate) fn create_keypair(addr: EmailAddress) -> Result<KeyPair> {
         .preferred_compression_algorithms(smallvec![
             CompressionAlgorithm::ZLIB,
             CompressionAlgorithm::ZIP,
-        ])
-        .subkey(
-            SubkeyParamsBuilder::default()
-                .key_type(encryption_key_type)
-                .can_encrypt(true)
-                .passphrase(None)
-                .build()
-                .context("failed to build subkey parameters")?,
-        )
-        .build()
-        .context("failed to build key parameters")?;
+        ]);
+    if let Some(valid_for) = params.valid_for {
+        builder.key_expiration_time(Some(valid_for));
+    }
+
+    let mut subkey_builder = SubkeyParamsBuilder::default();
+    subkey_builder
+        .key_type(params.subkey_type)
+        .can_encrypt(true)
+        .passphrase(None);
+    if let Some(valid_for) = params.valid_for {
+        subkey_builder.key_expiration_time(Some(valid_for));
+    }
+    builder.subkey(
+        subkey_builder
+            .build()
+            .context("failed to build subkey parameters")?,
+    );
+    let key_params = builder.build().context("failed to build key parameters")?;
 
     let mut rng = thread_rng();
     let secret_key = key_params
@@ -148,24 +280,217 @@ pub(crate) fn create_keypair(addr: EmailAddress) -> Result<KeyPair> {
     Ok(key_pair)
 }
 
+/// Serializes a (sub)key's packet body the way [RFC 4880 §5.2.4](https://www.rfc-editor.org/rfc/rfc4880#section-5.2.4)
+/// requires it to be fed into a certification/binding signature's hash: tag
+/// byte `0x99` followed by a two-byte big-endian body length, followed by
+/// the body itself, regardless of how the key is actually framed on the wire.
+fn key_signing_preimage(key: &impl PublicKeyTrait) -> Result<Vec<u8>> {
+    let mut body = Vec::new();
+    key.to_writer_old(&mut body)
+        .context("failed to serialize key for signing")?;
+    let mut preimage = vec![0x99u8];
+    preimage.extend_from_slice(&(body.len() as u16).to_be_bytes());
+    preimage.extend_from_slice(&body);
+    Ok(preimage)
+}
+
+/// Builds the preimage a subkey binding signature is hashed over: the
+/// primary key's body followed by the subkey's body, each framed per
+/// [`key_signing_preimage`].
+fn subkey_binding_preimage(
+    primary: &impl PublicKeyTrait,
+    subkey: &impl PublicKeyTrait,
+) -> Result<Vec<u8>> {
+    let mut preimage = key_signing_preimage(primary)?;
+    preimage.extend_from_slice(&key_signing_preimage(subkey)?);
+    Ok(preimage)
+}
+
+/// Re-signs `existing`'s encryption subkey binding signature to carry a key
+/// expiration time of `new_valid_for`, counted from the subkey's creation
+/// time, preserving the key's
+/// fingerprint so peers keep trusting the same key.
+///
+/// This is how key tooling usually treats expiration: a renewable property
+/// of the self-signature rather than an immutable property of the key.
+///
+/// rpgp's [`SecretKeyParamsBuilder`]/[`SubkeyParamsBuilder`] only let you set
+/// `key_expiration_time` at fresh key *generation*; there is no builder for
+/// re-certifying an already-generated [`SignedSecretKey`]. So instead of
+/// re-signing the existing (unmodifiable) certification, this rebuilds the
+/// subkey binding signature by hand with [`SignatureConfig`] — the same
+/// low-level construction [`pk_calc_signature`] already uses for detached
+/// signatures, but hashed over the binding-signature preimage (see
+/// [`subkey_binding_preimage`]) instead of over document bytes, with a fresh
+/// `SubpacketData::KeyExpirationTime(new_valid_for)` in place of the
+/// previous one.
+///
+/// Only the encryption subkey's binding signature is rebuilt, not the
+/// primary key's user ID self-certification: [`is_expired`] and
+/// [`select_pk_for_encryption`], the only places this crate checks key
+/// expiration, look at the subkey's own binding signature, so that is the
+/// signature that actually needs the new expiration for renewal to have any
+/// observable effect here.
+pub fn renew_keypair(
+    existing: &SignedSecretKey,
+    new_valid_for: std::time::Duration,
+) -> Result<KeyPair> {
+    let rng = thread_rng();
+    let fingerprint_before = existing.fingerprint();
+
+    let mut renewed = existing.clone();
+
+    let subkey_index = renewed
+        .secret_subkeys
+        .iter()
+        .position(|subkey| subkey.key.is_encryption_key())
+        .context("key has no encryption subkey to renew")?;
+
+    let preimage =
+        subkey_binding_preimage(&renewed.primary_key, &renewed.secret_subkeys[subkey_index].key)?;
+
+    let mut config =
+        SignatureConfig::from_key(rng, &renewed.primary_key, SignatureType::SubkeyBinding)?;
+    config.hashed_subpackets = vec![
+        Subpacket::regular(SubpacketData::IssuerFingerprint(fingerprint_before.clone()))?,
+        Subpacket::critical(SubpacketData::SignatureCreationTime(
+            chrono::Utc::now().trunc_subsecs(0),
+        ))?,
+        Subpacket::critical(SubpacketData::KeyExpirationTime(new_valid_for))?,
+    ];
+    config.unhashed_subpackets = vec![Subpacket::regular(SubpacketData::Issuer(
+        renewed.primary_key.key_id(),
+    ))?];
+
+    let signature = config.sign_with(HASH_ALGORITHM, &preimage, |hash, digest| {
+        renewed
+            .primary_key
+            .create_signature(&Password::empty(), hash, digest)
+            .context("failed to sign renewed subkey binding")
+    })?;
+
+    renewed.secret_subkeys[subkey_index].details.signatures = vec![signature];
+
+    renewed
+        .verify()
+        .context("invalid key produced by renewal")?;
+
+    anyhow::ensure!(
+        renewed.fingerprint() == fingerprint_before,
+        "renewal must preserve the key's fingerprint"
+    );
+
+    let renewed_pair = KeyPair::new(renewed)?;
+    let renewed_subkey = select_pk_for_encryption(&renewed_pair.public)
+        .context("renewed key unexpectedly lost its encryption subkey")?;
+    let renewed_expiration = renewed_subkey
+        .details
+        .signatures
+        .iter()
+        .max_by_key(|sig| sig.signature_creation_time())
+        .and_then(|sig| sig.key_expiration_time());
+    anyhow::ensure!(
+        renewed_expiration == Some(new_valid_for),
+        "renewed subkey binding does not carry the expected expiration"
+    );
+
+    Ok(renewed_pair)
+}
+
 /// Selects a subkey of the public key to use for encryption.
 ///
-/// Returns `None` if the public key cannot be used for encryption.
+/// Returns `None` if the public key cannot be used for encryption, e.g.
+/// because none of its subkeys carry the encryption key flag, or all
+/// encryption-capable subkeys have expired.
 ///
-/// TODO: take key flags and expiration dates into account
+/// Among the encryption-capable, non-expired subkeys, prefers (in order):
+/// 1. the most recently created subkey (so a rotated/re-certified key wins
+///    over an older one that is still technically valid),
+/// 2. ties broken by key ID, for determinism.
 fn select_pk_for_encryption(key: &SignedPublicKey) -> Option<&SignedPublicSubKey> {
+    let now = chrono::Utc::now();
+
     key.public_subkeys
         .iter()
-        .find(|subkey| subkey.is_encryption_key())
+        .filter(|subkey| subkey.is_encryption_key())
+        .filter(|subkey| !is_expired(subkey, now))
+        .max_by_key(|subkey| (subkey.public_key.created_at(), subkey.public_key.key_id()))
+}
+
+/// Returns whether a (sub)key's self-signature has an expiration time that
+/// has already passed.
+///
+/// A key can carry more than one self-signature if it was re-certified (see
+/// [`renew_keypair`]) or simply re-signed by other tooling over its
+/// lifetime, so this looks at the most recent one (by `SignatureCreationTime`)
+/// rather than the most restrictive expiration across all of them: otherwise
+/// a superseded signature's shorter expiration would keep outvoting a later,
+/// validly-extended one.
+fn is_expired(subkey: &SignedPublicSubKey, now: chrono::DateTime<chrono::Utc>) -> bool {
+    subkey
+        .details
+        .signatures
+        .iter()
+        .max_by_key(|sig| sig.signature_creation_time())
+        .and_then(|sig| sig.key_expiration_time())
+        .is_some_and(|expires_in| subkey.public_key.created_at() + expires_in < now)
 }
 
-/// Encrypts `plain` text using `public_keys_for_encryption`
+/// Reads back each recipient's own `preferred_symmetric_algorithms`
+/// subpacket (as written by `create_keypair_with_params` at key-generation
+/// time) off their selected encryption subkey, and intersects them to pick
+/// the strongest algorithm every recipient actually supports. A recipient
+/// advertising no preference at all falls back to AES-128, per the OpenPGP
+/// spec's implicit-default rule.
+///
+/// This negotiates from the recipients' own key material, which is a
+/// different input from [`CryptoPreference::negotiate`]: that one negotiates
+/// each *node's* advertised [`CryptoPreference`] (exchanged out of band via
+/// `Aheader`/`Peerstate`). Callers that already have a negotiated
+/// `CryptoPreference` (e.g. `EncryptHelper::encrypt`) should keep using that;
+/// callers with nothing but keys (e.g. `sop::encrypt`) should use this
+/// instead of hardcoding [`SYMMETRIC_KEY_ALGORITHM`].
+pub fn select_symmetric_algorithm_for_keys(
+    recipients: &[SignedPublicKey],
+) -> SymmetricKeyAlgorithm {
+    let mut common: Option<Vec<SymmetricKeyAlgorithm>> = None;
+    for key in recipients {
+        let preferred = select_pk_for_encryption(key)
+            .and_then(|subkey| {
+                subkey
+                    .details
+                    .signatures
+                    .iter()
+                    .find_map(|sig| sig.preferred_symmetric_algorithms())
+                    .map(|algs| algs.to_vec())
+            })
+            .unwrap_or_else(|| vec![SymmetricKeyAlgorithm::AES128]);
+
+        common = Some(match common {
+            None => preferred,
+            Some(existing) => existing
+                .into_iter()
+                .filter(|alg| preferred.contains(alg))
+                .collect(),
+        });
+    }
+    common
+        .and_then(|algs| algs.into_iter().next())
+        .unwrap_or(SymmetricKeyAlgorithm::AES128)
+}
+
+/// Encrypts `plain` text using `public_keys_for_encryption`
 /// and signs it using `private_key_for_signing`.
+///
+/// `symmetric_algorithm` is the negotiated cipher (see [`CryptoPreference::negotiate`]
+/// and [`select_symmetric_algorithm_for_keys`]); pass [`SYMMETRIC_KEY_ALGORITHM`]
+/// to keep the previous fixed-AES128 behavior.
 pub async fn pk_encrypt(
     plain: Vec<u8>,
     public_keys_for_encryption: Vec<SignedPublicKey>,
     private_key_for_signing: Option<SignedSecretKey>,
     compress: bool,
+    symmetric_algorithm: SymmetricKeyAlgorithm,
 ) -> Result<String> {
     Handle::current()
         .spawn_blocking(move || {
@@ -176,7 +501,7 @@ pub async fn pk_encrypt(
                 .filter_map(select_pk_for_encryption);
 
             let msg = MessageBuilder::from_bytes("", plain);
-            let mut msg = msg.seipd_v1(&mut rng, SYMMETRIC_KEY_ALGORITHM);
+            let mut msg = msg.seipd_v1(&mut rng, symmetric_algorithm);
             for pkey in pkeys {
                 msg.encrypt_to_key(&mut rng, &pkey)?;
             }
@@ -195,36 +520,69 @@ pub async fn pk_encrypt(
         .await?
 }
 
-/// Produces a detached signature for `plain` text using `private_key_for_signing`.
-pub fn pk_calc_signature(
-    plain: Vec<u8>,
-    private_key_for_signing: &SignedSecretKey,
-) -> Result<String> {
+/// Abstracts over where a signing key's private material lives.
+///
+/// The default implementation, [`InMemorySigner`], wraps a [`SignedSecretKey`]
+/// held in process memory, which is how every caller worked before this
+/// abstraction existed. A future implementation can instead forward the
+/// hashed digest to an external gpg-agent-style socket or a smartcard, so the
+/// secret key material never enters this crate's address space, while the
+/// armored output and issuer/fingerprint subpackets stay identical.
+pub trait Signer: Send + Sync {
+    /// The signer's public key, used to build issuer/fingerprint subpackets.
+    fn public(&self) -> &SignedPublicKey;
+
+    /// Signs a pre-computed `digest` (hashed with `hash`), returning the raw
+    /// MPI-encoded signature bytes.
+    fn sign_digest(&self, hash: HashAlgorithm, digest: &[u8]) -> Result<Vec<u8>>;
+}
+
+/// A [`Signer`] backed by a [`SignedSecretKey`] held in process memory.
+pub struct InMemorySigner {
+    secret: SignedSecretKey,
+    public: SignedPublicKey,
+}
+
+impl InMemorySigner {
+    /// Wraps `secret`, splitting off its public key for [`Signer::public`].
+    pub fn new(secret: SignedSecretKey) -> Result<Self> {
+        let public = secret.split_public_key()?;
+        Ok(Self { secret, public })
+    }
+}
+
+impl Signer for InMemorySigner {
+    fn public(&self) -> &SignedPublicKey {
+        &self.public
+    }
+
+    fn sign_digest(&self, hash: HashAlgorithm, digest: &[u8]) -> Result<Vec<u8>> {
+        self.secret
+            .primary_key
+            .create_signature(&Password::empty(), hash, digest)
+            .context("failed to sign digest")
+    }
+}
+
+/// Produces a detached signature for `plain` text using `signer`.
+pub fn pk_calc_signature(plain: Vec<u8>, signer: &dyn Signer) -> Result<String> {
     let rng = thread_rng();
+    let public = signer.public();
 
-    let mut config = SignatureConfig::from_key(
-        rng,
-        &private_key_for_signing.primary_key,
-        SignatureType::Binary,
-    )?;
+    let mut config = SignatureConfig::from_key(rng, &public.primary_key, SignatureType::Binary)?;
 
     config.hashed_subpackets = vec![
-        Subpacket::regular(SubpacketData::IssuerFingerprint(
-            private_key_for_signing.fingerprint(),
-        ))?,
+        Subpacket::regular(SubpacketData::IssuerFingerprint(public.fingerprint()))?,
         Subpacket::critical(SubpacketData::SignatureCreationTime(
             chrono::Utc::now().trunc_subsecs(0),
         ))?,
     ];
-    config.unhashed_subpackets = vec![Subpacket::regular(SubpacketData::Issuer(
-        private_key_for_signing.key_id(),
-    ))?];
+    config.unhashed_subpackets =
+        vec![Subpacket::regular(SubpacketData::Issuer(public.key_id()))?];
 
-    let signature = config.sign(
-        &private_key_for_signing.primary_key,
-        &Password::empty(),
-        plain.as_slice(),
-    )?;
+    let signature = config.sign_with(HASH_ALGORITHM, plain.as_slice(), |hash, digest| {
+        signer.sign_digest(hash, digest)
+    })?;
 
     let sig = StandaloneSignature::new(signature);
 
@@ -347,6 +705,108 @@ mod tests {
     use super::*;
     use crate::test_utils::{alice_keypair, bob_keypair};
 
+    #[test]
+    fn test_is_expired_uses_most_recent_signature_not_minimum() {
+        let rng = thread_rng();
+        let alice = alice_keypair();
+        let subkey_index = alice
+            .public
+            .public_subkeys
+            .iter()
+            .position(|subkey| subkey.is_encryption_key())
+            .unwrap();
+        let primary = &alice.public.primary_key;
+        let subkey_key = &alice.public.public_subkeys[subkey_index].public_key;
+        let preimage = subkey_binding_preimage(primary, subkey_key).unwrap();
+        let fingerprint = alice.public.fingerprint();
+
+        let sign = |created: chrono::DateTime<chrono::Utc>, expires: std::time::Duration| {
+            let mut config =
+                SignatureConfig::from_key(rng.clone(), primary, SignatureType::SubkeyBinding)
+                    .unwrap();
+            config.hashed_subpackets = vec![
+                Subpacket::regular(SubpacketData::IssuerFingerprint(fingerprint.clone())).unwrap(),
+                Subpacket::critical(SubpacketData::SignatureCreationTime(created)).unwrap(),
+                Subpacket::critical(SubpacketData::KeyExpirationTime(expires)).unwrap(),
+            ];
+            config
+                .sign_with(HASH_ALGORITHM, &preimage, |hash, digest| {
+                    alice
+                        .secret
+                        .primary_key
+                        .create_signature(&Password::empty(), hash, digest)
+                        .context("failed to sign")
+                })
+                .unwrap()
+        };
+
+        let now = chrono::Utc::now();
+        // A short-lived signature (already expired) superseded two days ago
+        // by a long-lived one: the old signature's expiration is the smaller
+        // of the two, but it is not the current self-certification, so it
+        // must not decide whether the subkey is considered expired.
+        let superseded = sign(now - chrono::Duration::days(2), std::time::Duration::from_secs(3600));
+        let current = sign(
+            now - chrono::Duration::days(1),
+            std::time::Duration::from_secs(3 * 365 * 24 * 3600),
+        );
+
+        let mut subkey = alice.public.public_subkeys[subkey_index].clone();
+        subkey.details.signatures = vec![superseded, current];
+
+        assert!(!is_expired(&subkey, now));
+    }
+
+    #[test]
+    fn test_select_pk_for_encryption_picks_encryption_subkey() {
+        let alice = alice_keypair();
+        let subkey = select_pk_for_encryption(&alice.public);
+        assert!(subkey.is_some());
+    }
+
+    #[test]
+    fn test_crypto_preference_negotiate() {
+        let aes_only = CryptoPreference {
+            ordered: vec![SymmetricKeyAlgorithm::AES256, SymmetricKeyAlgorithm::AES128],
+        };
+        let chacha_first = CryptoPreference {
+            ordered: vec![SymmetricKeyAlgorithm::ChaCha20, SymmetricKeyAlgorithm::AES256],
+        };
+
+        // Only AES256 is common to both lists.
+        assert_eq!(
+            CryptoPreference::negotiate(&aes_only, [&chacha_first]),
+            SymmetricKeyAlgorithm::AES256
+        );
+
+        // No recipients: our own preference wins outright.
+        assert_eq!(
+            CryptoPreference::negotiate(&chacha_first, []),
+            SymmetricKeyAlgorithm::ChaCha20
+        );
+
+        // Disjoint preferences fall back to AES256.
+        let aes128_only = CryptoPreference {
+            ordered: vec![SymmetricKeyAlgorithm::AES128],
+        };
+        let chacha_only = CryptoPreference {
+            ordered: vec![SymmetricKeyAlgorithm::ChaCha20],
+        };
+        assert_eq!(
+            CryptoPreference::negotiate(&aes128_only, [&chacha_only]),
+            SymmetricKeyAlgorithm::AES256
+        );
+    }
+
+    #[test]
+    fn test_crypto_preference_benchmark_covers_all_algorithms() {
+        let pref = CryptoPreference::benchmark();
+        assert_eq!(pref.ordered.len(), BENCHMARKED_ALGORITHMS.len());
+        for alg in BENCHMARKED_ALGORITHMS {
+            assert!(pref.ordered.contains(&alg));
+        }
+    }
+
     fn pk_decrypt_and_validate<'a>(
         ctext: &'a [u8],
         private_keys_for_decryption: &'a [SignedSecretKey],
@@ -398,6 +858,60 @@ mod tests {
         assert_ne!(keypair0.public, keypair1.public);
     }
 
+    #[test]
+    fn test_create_keypair_non_expiring() {
+        let params = KeyGenParams {
+            valid_for: None,
+            ..KeyGenParams::default()
+        };
+        let keypair =
+            create_keypair_with_params(EmailAddress::new("foo@bar.de").unwrap(), params).unwrap();
+        keypair.public.verify().unwrap();
+    }
+
+    #[test]
+    fn test_renew_keypair_preserves_fingerprint() {
+        let keypair = create_keypair(EmailAddress::new("foo@bar.de").unwrap()).unwrap();
+        let renewed = renew_keypair(&keypair.secret, DEFAULT_KEY_VALIDITY).unwrap();
+        assert_eq!(renewed.public.fingerprint(), keypair.public.fingerprint());
+    }
+
+    #[test]
+    fn test_renew_keypair_extends_expiration() {
+        let keypair = create_keypair(EmailAddress::new("foo@bar.de").unwrap()).unwrap();
+        let extended_validity = DEFAULT_KEY_VALIDITY + std::time::Duration::from_secs(3600);
+        let renewed = renew_keypair(&keypair.secret, extended_validity).unwrap();
+
+        let subkey = select_pk_for_encryption(&renewed.public).unwrap();
+        let expiration = subkey
+            .details
+            .signatures
+            .iter()
+            .max_by_key(|sig| sig.signature_creation_time())
+            .and_then(|sig| sig.key_expiration_time());
+        assert_eq!(expiration, Some(extended_validity));
+    }
+
+    #[test]
+    fn test_renew_keypair_rejects_key_without_encryption_subkey() {
+        let mut rng = thread_rng();
+        let mut builder = SecretKeyParamsBuilder::default();
+        builder
+            .key_type(PgpKeyType::Ed25519Legacy)
+            .can_certify(true)
+            .can_sign(true)
+            .primary_user_id("<signing-only@bar.de>".to_string())
+            .passphrase(None);
+        let key_params = builder.build().unwrap();
+        let secret_key = key_params
+            .generate(&mut rng)
+            .unwrap()
+            .sign(&mut rng, &Password::empty())
+            .unwrap();
+
+        assert!(renew_keypair(&secret_key, DEFAULT_KEY_VALIDITY).is_err());
+    }
+
     /// [SignedSecretKey] and [SignedPublicKey] objects
     /// to use in tests.
     struct TestKeys {
@@ -441,6 +955,7 @@ mod tests {
                     keyring,
                     Some(KEYS.alice_secret.clone()),
                     compress,
+                    SYMMETRIC_KEY_ALGORITHM,
                 )
                 .await
                 .unwrap()
@@ -455,8 +970,14 @@ mod tests {
                 let keyring = vec![KEYS.alice_public.clone(), KEYS.bob_public.clone()];
                 let compress = true;
 
-                pk_encrypt(CLEARTEXT.to_vec(), keyring, None, compress)
-                    .await
+                pk_encrypt(
+                    CLEARTEXT.to_vec(),
+                    keyring,
+                    None,
+                    compress,
+                    SYMMETRIC_KEY_ALGORITHM,
+                )
+                .await
                     .unwrap()
             })
             .await
@@ -0,0 +1,424 @@
+//! Double-ratchet forward secrecy layer, layered under the existing PGP
+//! envelope for 1:1 encrypted chats.
+//!
+//! A stolen long-term private key should not decrypt the entire message
+//! history. [`RatchetState`] is meant to be kept per-contact inside
+//! [`crate::peerstate::Peerstate`] (not yet wired up in this tree, see
+//! [`crate::e2ee::encrypt_with_ratchet`]'s doc comment) and derives a fresh
+//! one-time message key for every outgoing message (symmetric-ratchet
+//! forward secrecy), and performs a DH ratchet step whenever a new ephemeral
+//! public key arrives in a peer's header. The long-term PGP key continues to
+//! sign headers, so the ratchet remains authenticated and compatible with
+//! `should_encrypt`/Autocrypt.
+
+use std::collections::HashMap;
+
+use anyhow::{Context as _, Result};
+use hkdf::Hkdf;
+use pgp::crypto::sym::SymmetricKeyAlgorithm;
+use sha2::{Digest, Sha256};
+use x25519_dalek::{EphemeralSecret, PublicKey, StaticSecret};
+
+/// Symmetric algorithm used to seal one message key's worth of plaintext.
+/// AES-256's 32-byte key matches the 32-byte message keys this module derives.
+const MESSAGE_SYMMETRIC_ALGORITHM: SymmetricKeyAlgorithm = SymmetricKeyAlgorithm::AES256;
+
+/// Maximum number of skipped message keys retained to tolerate out-of-order
+/// IMAP delivery. Bounded so a malicious peer cannot force unbounded memory growth.
+const MAX_SKIPPED_KEYS: usize = 100;
+
+/// A one-time message key, identified by the DH public key in use at the time
+/// plus the chain index it was derived at.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct SkippedKeyId {
+    dh_public: [u8; 32],
+    chain_index: u64,
+}
+
+/// Double-ratchet state for a single contact.
+#[derive(Debug)]
+pub struct RatchetState {
+    root_key: [u8; 32],
+    sending_chain_key: Option<[u8; 32]>,
+    receiving_chain_key: Option<[u8; 32]>,
+    /// Our current DH keypair, advertised in the next outgoing message header.
+    our_dh_secret: StaticSecret,
+    our_dh_public: PublicKey,
+    /// The peer's most recently seen DH public key, used to detect ratchet steps.
+    their_dh_public: Option<PublicKey>,
+    sending_chain_index: u64,
+    receiving_chain_index: u64,
+    /// Skipped message keys, bounded by [`MAX_SKIPPED_KEYS`], oldest evicted first.
+    skipped_keys: HashMap<SkippedKeyId, [u8; 32]>,
+    skipped_order: Vec<SkippedKeyId>,
+}
+
+impl RatchetState {
+    /// Initializes a fresh ratchet from a shared secret established out of
+    /// band (e.g. derived from the initial PGP-encrypted handshake).
+    pub fn new(shared_secret: [u8; 32]) -> Self {
+        let our_dh_secret = StaticSecret::random_from_rng(rand::rngs::OsRng);
+        let our_dh_public = PublicKey::from(&our_dh_secret);
+        Self {
+            root_key: shared_secret,
+            sending_chain_key: None,
+            receiving_chain_key: None,
+            our_dh_secret,
+            our_dh_public,
+            their_dh_public: None,
+            sending_chain_index: 0,
+            receiving_chain_index: 0,
+            skipped_keys: HashMap::new(),
+            skipped_order: Vec::new(),
+        }
+    }
+
+    /// Our current DH public key, to be advertised in the next outgoing message header.
+    pub fn our_dh_public(&self) -> [u8; 32] {
+        self.our_dh_public.to_bytes()
+    }
+
+    /// Bootstraps the ratchet for the initiator side of a 1:1 conversation:
+    /// the side that sends the first message has no incoming header yet to
+    /// learn the peer's DH public key from, so there is no other way to
+    /// seed `their_dh_public` before calling [`Self::next_sending_key`].
+    ///
+    /// `peer_dh_public` is the peer's initial DH public key, obtained out of
+    /// band the same way `shared_secret` was (e.g. alongside their key in
+    /// the initial PGP-encrypted handshake). A responder never calls this:
+    /// it reaches the equivalent state organically the first time it calls
+    /// [`Self::message_key_for`] on an incoming message.
+    pub fn establish_as_initiator(&mut self, peer_dh_public: [u8; 32]) -> Result<()> {
+        self.their_dh_public = Some(PublicKey::from(peer_dh_public));
+        self.dh_ratchet_sending()
+    }
+
+    /// Advances the sending chain to produce a one-time message key for the
+    /// next outgoing message, deleting the previous chain key (forward secrecy).
+    pub fn next_sending_key(&mut self) -> Result<[u8; 32]> {
+        if self.sending_chain_key.is_none() {
+            self.dh_ratchet_sending()?;
+        }
+        let chain_key = self
+            .sending_chain_key
+            .as_ref()
+            .context("sending chain not yet initialized")?;
+        let (new_chain_key, message_key) = Self::advance_chain(chain_key);
+        self.sending_chain_key = Some(new_chain_key);
+        self.sending_chain_index += 1;
+        Ok(message_key)
+    }
+
+    /// Derives the message key for an incoming message, performing a DH
+    /// ratchet step first if `their_dh_public` is new, and caching
+    /// (bounded) any keys for messages skipped along the way so
+    /// out-of-order delivery can still be decrypted later.
+    ///
+    /// Errors if `chain_index` is behind the current receiving chain and not
+    /// in `skipped_keys`: that message was already consumed in-line, so the
+    /// key for it is gone (by design, for forward secrecy) rather than
+    /// derivable again from the current chain position.
+    pub fn message_key_for(
+        &mut self,
+        their_dh_public: [u8; 32],
+        chain_index: u64,
+    ) -> Result<[u8; 32]> {
+        let their_dh_public = PublicKey::from(their_dh_public);
+
+        if self.their_dh_public != Some(their_dh_public) {
+            self.dh_ratchet_receiving(their_dh_public)?;
+        }
+
+        while self.receiving_chain_index < chain_index {
+            let chain_key = self
+                .receiving_chain_key
+                .as_ref()
+                .context("receiving chain not yet initialized")?;
+            let (new_chain_key, message_key) = Self::advance_chain(chain_key);
+            self.cache_skipped_key(their_dh_public, self.receiving_chain_index, message_key);
+            self.receiving_chain_key = Some(new_chain_key);
+            self.receiving_chain_index += 1;
+        }
+
+        let skipped_id = SkippedKeyId {
+            dh_public: their_dh_public.to_bytes(),
+            chain_index,
+        };
+        if let Some(key) = self.skipped_keys.remove(&skipped_id) {
+            self.skipped_order.retain(|id| id != &skipped_id);
+            return Ok(key);
+        }
+
+        anyhow::ensure!(
+            chain_index >= self.receiving_chain_index,
+            "message key for chain index {chain_index} already used or not cached"
+        );
+
+        let chain_key = self
+            .receiving_chain_key
+            .as_ref()
+            .context("receiving chain not yet initialized")?;
+        let (new_chain_key, message_key) = Self::advance_chain(chain_key);
+        self.receiving_chain_key = Some(new_chain_key);
+        self.receiving_chain_index += 1;
+        Ok(message_key)
+    }
+
+    /// Seals an outgoing message under the next one-time message key (see
+    /// [`Self::next_sending_key`]), returning the chain index it was sealed
+    /// at alongside the sealed bytes: the recipient needs that index to
+    /// derive the matching key via [`Self::open_message`].
+    pub fn seal_message(&mut self, plaintext: &[u8]) -> Result<(u64, Vec<u8>)> {
+        let chain_index = self.sending_chain_index;
+        let message_key = self.next_sending_key()?;
+        Ok((chain_index, seal_with_message_key(&message_key, plaintext)?))
+    }
+
+    /// Inverse of [`Self::seal_message`]: derives the message key for
+    /// `chain_index` via [`Self::message_key_for`] and opens `sealed` with it.
+    pub fn open_message(
+        &mut self,
+        their_dh_public: [u8; 32],
+        chain_index: u64,
+        sealed: &[u8],
+    ) -> Result<Vec<u8>> {
+        let message_key = self.message_key_for(their_dh_public, chain_index)?;
+        open_with_message_key(&message_key, sealed)
+    }
+
+    fn cache_skipped_key(&mut self, dh_public: PublicKey, chain_index: u64, key: [u8; 32]) {
+        let id = SkippedKeyId {
+            dh_public: dh_public.to_bytes(),
+            chain_index,
+        };
+        if self.skipped_keys.len() >= MAX_SKIPPED_KEYS {
+            if let Some(oldest) = self.skipped_order.first().cloned() {
+                self.skipped_keys.remove(&oldest);
+                self.skipped_order.remove(0);
+            }
+        }
+        self.skipped_keys.insert(id.clone(), key);
+        self.skipped_order.push(id);
+    }
+
+    /// Performs a DH ratchet step on receipt of a new peer ephemeral key:
+    /// `root_key, new_chain = HKDF(root_key, DH(our_priv, their_pub))`,
+    /// then generates a fresh DH keypair to advertise next.
+    fn dh_ratchet_receiving(&mut self, their_dh_public: PublicKey) -> Result<()> {
+        let dh_output = self.our_dh_secret.diffie_hellman(&their_dh_public);
+        let (new_root, new_chain) = Self::kdf_root(&self.root_key, dh_output.as_bytes());
+        self.root_key = new_root;
+        self.receiving_chain_key = Some(new_chain);
+        self.receiving_chain_index = 0;
+        self.their_dh_public = Some(their_dh_public);
+
+        // Generate our next DH keypair and ratchet the sending chain too.
+        self.our_dh_secret = StaticSecret::random_from_rng(rand::rngs::OsRng);
+        self.our_dh_public = PublicKey::from(&self.our_dh_secret);
+        self.dh_ratchet_sending()?;
+        Ok(())
+    }
+
+    fn dh_ratchet_sending(&mut self) -> Result<()> {
+        let their_dh_public = self
+            .their_dh_public
+            .context("cannot ratchet sending chain before first receiving ratchet step")?;
+        let dh_output = self.our_dh_secret.diffie_hellman(&their_dh_public);
+        let (new_root, new_chain) = Self::kdf_root(&self.root_key, dh_output.as_bytes());
+        self.root_key = new_root;
+        self.sending_chain_key = Some(new_chain);
+        self.sending_chain_index = 0;
+        Ok(())
+    }
+
+    fn kdf_root(root_key: &[u8; 32], dh_output: &[u8; 32]) -> ([u8; 32], [u8; 32]) {
+        let hk = Hkdf::<Sha256>::new(Some(root_key), dh_output);
+        let mut okm = [0u8; 64];
+        hk.expand(b"dc double ratchet root", &mut okm)
+            .expect("64 bytes is a valid HKDF output length");
+        let mut new_root = [0u8; 32];
+        let mut new_chain = [0u8; 32];
+        new_root.copy_from_slice(&okm[..32]);
+        new_chain.copy_from_slice(&okm[32..]);
+        (new_root, new_chain)
+    }
+
+    /// Advances a chain key, returning `(new_chain_key, message_key)`.
+    /// Per-message keys are always derived via HKDF so the old chain key
+    /// can be discarded immediately after use.
+    fn advance_chain(chain_key: &[u8; 32]) -> ([u8; 32], [u8; 32]) {
+        let hk = Hkdf::<Sha256>::new(None, chain_key);
+        let mut okm = [0u8; 64];
+        hk.expand(b"dc double ratchet chain", &mut okm)
+            .expect("64 bytes is a valid HKDF output length");
+        let mut new_chain_key = [0u8; 32];
+        let mut message_key = [0u8; 32];
+        new_chain_key.copy_from_slice(&okm[..32]);
+        message_key.copy_from_slice(&okm[32..]);
+        (new_chain_key, message_key)
+    }
+}
+
+/// Seals `plaintext` under a one-time `message_key`: encrypt-then-MAC, the
+/// same construction [`crate::mls::GroupEncryptHelper`] uses to authenticate
+/// its sealed payloads, since a bare block cipher provides no integrity.
+fn seal_with_message_key(message_key: &[u8; 32], plaintext: &[u8]) -> Result<Vec<u8>> {
+    let (encryption_key, mac_key) = split_message_key(message_key)?;
+    let mut sealed = plaintext.to_vec();
+    pgp::crypto::sym::encrypt(MESSAGE_SYMMETRIC_ALGORITHM, &encryption_key, &mut sealed)
+        .map_err(|_| anyhow::anyhow!("failed to seal message"))?;
+    let mac = hmac_sha256(&mac_key, &sealed);
+    sealed.extend_from_slice(&mac);
+    Ok(sealed)
+}
+
+/// Inverse of [`seal_with_message_key`]; fails closed on a MAC mismatch
+/// instead of returning garbage plaintext.
+fn open_with_message_key(message_key: &[u8; 32], sealed: &[u8]) -> Result<Vec<u8>> {
+    anyhow::ensure!(sealed.len() >= 32, "sealed message too short to contain a MAC");
+    let (ciphertext, mac) = sealed.split_at(sealed.len() - 32);
+
+    let (encryption_key, mac_key) = split_message_key(message_key)?;
+    let expected_mac = hmac_sha256(&mac_key, ciphertext);
+    anyhow::ensure!(
+        ct_eq(&expected_mac, mac),
+        "message failed authentication, possibly tampered with in transit"
+    );
+
+    let mut plaintext = ciphertext.to_vec();
+    pgp::crypto::sym::decrypt(MESSAGE_SYMMETRIC_ALGORITHM, &encryption_key, &mut plaintext)
+        .map_err(|_| anyhow::anyhow!("failed to open message"))?;
+    Ok(plaintext)
+}
+
+/// Splits one 32-byte message key into an independent `(encryption key, MAC
+/// key)` pair via HKDF, so the same message key isn't reused directly as both.
+fn split_message_key(message_key: &[u8; 32]) -> Result<([u8; 32], [u8; 32])> {
+    let hk = Hkdf::<Sha256>::new(None, message_key);
+    let mut okm = [0u8; 64];
+    hk.expand(b"dc double ratchet message seal", &mut okm)
+        .map_err(|_| anyhow::anyhow!("HKDF expand failed"))?;
+    let mut encryption_key = [0u8; 32];
+    let mut mac_key = [0u8; 32];
+    encryption_key.copy_from_slice(&okm[..32]);
+    mac_key.copy_from_slice(&okm[32..]);
+    Ok((encryption_key, mac_key))
+}
+
+/// HMAC-SHA256 (RFC 2104), used to authenticate sealed message keys.
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    const BLOCK_SIZE: usize = 64;
+    let mut key_block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        let hashed = Sha256::digest(key);
+        key_block[..hashed.len()].copy_from_slice(&hashed);
+    } else {
+        key_block[..key.len()].copy_from_slice(key);
+    }
+
+    let mut ipad = [0x36u8; BLOCK_SIZE];
+    let mut opad = [0x5cu8; BLOCK_SIZE];
+    for i in 0..BLOCK_SIZE {
+        ipad[i] ^= key_block[i];
+        opad[i] ^= key_block[i];
+    }
+
+    let inner = Sha256::new().chain_update(ipad).chain_update(message).finalize();
+    Sha256::new()
+        .chain_update(opad)
+        .chain_update(inner)
+        .finalize()
+        .into()
+}
+
+/// Constant-time byte-slice comparison, so that checking a MAC doesn't leak
+/// how many leading bytes matched via a timing side channel.
+fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ratchet_roundtrip_in_order() {
+        let shared_secret = [7u8; 32];
+        let mut alice = RatchetState::new(shared_secret);
+        let mut bob = RatchetState::new(shared_secret);
+
+        // Alice is the initiator: she bootstraps via the public API using
+        // Bob's DH public key, obtained out of band. Bob, as the responder,
+        // reaches the matching state the first time he processes a message
+        // from Alice.
+        alice.establish_as_initiator(bob.our_dh_public()).unwrap();
+
+        let key1 = alice.next_sending_key().unwrap();
+        let key1_bob = bob.message_key_for(alice.our_dh_public(), 0).unwrap();
+        assert_eq!(key1, key1_bob);
+    }
+
+    #[test]
+    fn test_initiator_cannot_send_before_bootstrap() {
+        let mut alice = RatchetState::new([9u8; 32]);
+        assert!(alice.next_sending_key().is_err());
+    }
+
+    #[test]
+    fn test_message_key_for_rejects_already_consumed_chain_index() {
+        let shared_secret = [7u8; 32];
+        let mut alice = RatchetState::new(shared_secret);
+        let mut bob = RatchetState::new(shared_secret);
+        alice.establish_as_initiator(bob.our_dh_public()).unwrap();
+
+        alice.next_sending_key().unwrap();
+        bob.message_key_for(alice.our_dh_public(), 0).unwrap();
+
+        // A retried/duplicate delivery of the message at chain index 0: Bob
+        // already consumed it in-line (not via the skipped-key cache), so
+        // there is no key left to return for it.
+        assert!(bob.message_key_for(alice.our_dh_public(), 0).is_err());
+    }
+
+    #[test]
+    fn test_skipped_keys_bounded() {
+        let mut state = RatchetState::new([1u8; 32]);
+        state.receiving_chain_key = Some([2u8; 32]);
+        state.their_dh_public = Some(PublicKey::from([3u8; 32]));
+        for i in 0..(MAX_SKIPPED_KEYS as u64 + 10) {
+            state.cache_skipped_key(state.their_dh_public.unwrap(), i, [0u8; 32]);
+        }
+        assert!(state.skipped_keys.len() <= MAX_SKIPPED_KEYS);
+    }
+
+    #[test]
+    fn test_seal_open_message_roundtrips() {
+        let shared_secret = [7u8; 32];
+        let mut alice = RatchetState::new(shared_secret);
+        let mut bob = RatchetState::new(shared_secret);
+        alice.establish_as_initiator(bob.our_dh_public()).unwrap();
+
+        let (chain_index, sealed) = alice.seal_message(b"hello bob").unwrap();
+        let opened = bob
+            .open_message(alice.our_dh_public(), chain_index, &sealed)
+            .unwrap();
+        assert_eq!(opened, b"hello bob");
+    }
+
+    #[test]
+    fn test_open_message_rejects_tampered_ciphertext() {
+        let shared_secret = [7u8; 32];
+        let mut alice = RatchetState::new(shared_secret);
+        let mut bob = RatchetState::new(shared_secret);
+        alice.establish_as_initiator(bob.our_dh_public()).unwrap();
+
+        let (chain_index, mut sealed) = alice.seal_message(b"hello bob").unwrap();
+        sealed[0] ^= 0x01;
+        assert!(bob
+            .open_message(alice.our_dh_public(), chain_index, &sealed)
+            .is_err());
+    }
+}